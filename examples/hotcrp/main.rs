@@ -4,11 +4,19 @@ fn main() {
     let test_schema = include_str!("schema.yaml");
     let test_config = include_str!("config.yaml");
 
-    let graph = delf::read_yamls(&test_schema, &test_config);
+    let graph = match delf::read_yamls(&test_schema, &test_config) {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
 
     graph.validate();
 
     let id = "48".to_string();
 
-    graph.delete_object(&String::from("ContactInfo"), &id);
+    if let Err(e) = graph.delete_object(&String::from("ContactInfo"), &id) {
+        eprintln!("{}", e);
+    }
 }
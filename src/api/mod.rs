@@ -1,26 +1,245 @@
-use rocket::{delete, State, http::Status};
+use std::sync::Arc;
 
-use crate::graph::DelfGraph;
+use rocket::{delete, get, post, State, http::Status, response::{content, status::Custom}};
+
+use crate::graph::{plan::DeletionPlan, predicate::Predicate, DelfGraph, Deletion};
+use crate::jobs::{Job, JobQueue, JobTarget};
 use crate::DelfYamls;
 
-/// `DELETE` API endpoint to delete an object of the given type with specified ID.
+/// `DELETE` API endpoint to delete an object of the given type with specified ID, or to queue its deletion if a job queue is configured.
 #[delete("/object/<object_type>/<id>")]
-pub fn delete_object(object_type: String, id: String, yamls: State<DelfYamls>) -> Status {
-    let graph = DelfGraph::new(&yamls);
+pub fn delete_object(object_type: String, id: String, yamls: State<DelfYamls>, queue: Option<State<Arc<JobQueue>>>) -> Result<Custom<content::Json<String>>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
     if !graph.nodes.contains_key(&object_type) {
-        return Status::NotFound;
+        return Err(Status::NotFound);
+    }
+    match queue {
+        Some(queue) => enqueue_checked(&graph, graph.plan_delete_object(&object_type, &id), || {
+            enqueue(&queue, JobTarget::Object { object_type, id })
+        }),
+        None => match graph.delete_object(&object_type, &id) {
+            Ok(deletion) => Ok(Custom(Status::Ok, deletion_json(&deletion))),
+            Err(e) => Ok(refused(e)),
+        },
     }
-    graph.delete_object(&object_type, &id);
-    Status::Ok
 }
 
-/// `DELETE` API endpoint to delete an edge of the given type and the IDs of the objects it is connecting.
+/// `DELETE` API endpoint to delete an edge of the given type and the IDs of the objects it is connecting, or to queue its deletion if a job queue is configured.
 #[delete("/edge/<edge_type>/<from_id>/<to_id>")]
-pub fn delete_edge(edge_type: String, from_id: String, to_id: String, yamls: State<DelfYamls>) -> Status {
-    let graph = DelfGraph::new(&yamls);
-    if !graph.edges.contains_key(&edge_type) {
-        return Status::NotFound;
+pub fn delete_edge(edge_type: String, from_id: String, to_id: String, yamls: State<DelfYamls>, queue: Option<State<Arc<JobQueue>>>) -> Result<Custom<content::Json<String>>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.edge_exists(&edge_type, &from_id, &to_id) {
+        Ok(true) => (),
+        Ok(false) => return Err(Status::NotFound),
+        Err(_) => return Err(Status::InternalServerError),
+    }
+    match queue {
+        Some(queue) => enqueue_checked(&graph, graph.plan_delete_edge(&edge_type, &from_id, &to_id), || {
+            enqueue(&queue, JobTarget::Edge { edge_type, from_id, to_id })
+        }),
+        None => match graph.delete_edge(&edge_type, &from_id, &to_id) {
+            Ok(deletion) => Ok(Custom(Status::Ok, deletion_json(&deletion))),
+            Err(e) => Ok(refused(e)),
+        },
+    }
+}
+
+/// `DELETE` API endpoint to delete every instance of the given object type matching the `filter` predicate, or to queue their deletions if a job queue is configured.
+#[delete("/objects/<object_type>?<filter>")]
+pub fn delete_objects(object_type: String, filter: String, yamls: State<DelfYamls>, queue: Option<State<Arc<JobQueue>>>) -> Result<Custom<content::Json<String>>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    if !graph.nodes.contains_key(&object_type) {
+        return Err(Status::NotFound);
+    }
+    let predicate = match Predicate::parse(&filter) {
+        Ok(predicate) => predicate,
+        Err(e) => return Ok(refused(e)),
+    };
+    match queue {
+        Some(queue) => enqueue_checked(&graph, graph.plan_delete_where(&object_type, &predicate), || {
+            let ids = match graph.matching_ids(&object_type, &predicate) {
+                Ok(ids) => ids,
+                Err(e) => return Ok(refused(e)),
+            };
+            let mut jobs = Vec::new();
+            for id in ids.into_iter() {
+                match queue.enqueue(JobTarget::Object { object_type: object_type.clone(), id }) {
+                    Ok(job) => jobs.push(job),
+                    Err(_) => return Err(Status::InternalServerError),
+                }
+            }
+            Ok(Custom(Status::Accepted, content::Json(serde_json::to_string_pretty(&jobs).unwrap())))
+        }),
+        None => match graph.delete_where(&object_type, &predicate) {
+            Ok(bulk) => Ok(Custom(Status::Ok, content::Json(serde_json::to_string_pretty(&bulk).unwrap()))),
+            Err(e) => Ok(refused(e)),
+        },
+    }
+}
+
+/// `GET` API endpoint to get a deletion job, including its status and the deletion id once done.
+#[get("/job/<id>")]
+pub fn get_job(id: String, queue: State<Arc<JobQueue>>) -> Result<content::Json<String>, Status> {
+    match queue.get(&id) {
+        Some(job) => Ok(job_json(&job)),
+        None => Err(Status::NotFound),
+    }
+}
+
+/// `GET` API endpoint to list the deletion jobs which failed on every attempt.
+#[get("/jobs/dead")]
+pub fn dead_jobs(queue: State<Arc<JobQueue>>) -> content::Json<String> {
+    content::Json(serde_json::to_string_pretty(&queue.dead_letters()).unwrap())
+}
+
+/// `POST` API endpoint to move a deletion job from the dead letter list back into the queue.
+#[post("/job/<id>/retry")]
+pub fn retry_job(id: String, queue: State<Arc<JobQueue>>) -> Result<content::Json<String>, Status> {
+    match queue.retry(&id) {
+        Ok(job) => Ok(job_json(&job)),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+// Deletions over the limits are refused before they're queued
+fn enqueue_checked<F>(graph: &DelfGraph, plan: Result<DeletionPlan, String>, enqueue_jobs: F) -> Result<Custom<content::Json<String>>, Status>
+where
+    F: FnOnce() -> Result<Custom<content::Json<String>>, Status>,
+{
+    match plan.and_then(|plan| graph.check_limits(&plan)) {
+        Ok(()) => enqueue_jobs(),
+        Err(e) => Ok(refused(e)),
+    }
+}
+
+fn enqueue(queue: &JobQueue, target: JobTarget) -> Result<Custom<content::Json<String>>, Status> {
+    match queue.enqueue(target) {
+        Ok(job) => Ok(Custom(Status::Accepted, job_json(&job))),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+fn job_json(job: &Job) -> content::Json<String> {
+    content::Json(serde_json::to_string_pretty(job).unwrap())
+}
+
+fn deletion_json(deletion: &Deletion) -> content::Json<String> {
+    content::Json(serde_json::to_string_pretty(deletion).unwrap())
+}
+
+fn refused(error: String) -> Custom<content::Json<String>> {
+    Custom(Status::UnprocessableEntity, content::Json(serde_json::json!({ "error": error }).to_string()))
+}
+
+/// `GET` API endpoint to plan the deletion of an object of the given type with specified ID, returning the plan as json without deleting anything.
+#[get("/plan/object/<object_type>/<id>")]
+pub fn plan_object(object_type: String, id: String, yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.plan_delete_object(&object_type, &id) {
+        Ok(plan) => Ok(content::Json(plan.to_json())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `GET` API endpoint to plan the deletion of an edge of the given type and the IDs of the objects it is connecting, returning the plan as json without deleting anything.
+#[get("/plan/edge/<edge_type>/<from_id>/<to_id>")]
+pub fn plan_edge(edge_type: String, from_id: String, to_id: String, yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.plan_delete_edge(&edge_type, &from_id, &to_id) {
+        Ok(plan) => Ok(content::Json(plan.to_json())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `GET` API endpoint to list the legal holds placed.
+#[get("/holds")]
+pub fn list_holds(yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.holds() {
+        Ok(holds) => Ok(content::Json(serde_json::to_string_pretty(&holds).unwrap())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `POST` API endpoint to place a legal hold on an object of the given type with specified ID, with an optional reason.  Deletions skip held instances, and anything reached only through them.  Responds with the hold.
+#[post("/hold/<object_type>/<id>?<reason>")]
+pub fn place_hold(object_type: String, id: String, reason: Option<String>, yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.place_hold(&object_type, &id, reason) {
+        Ok(hold) => Ok(content::Json(serde_json::to_string_pretty(&hold).unwrap())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `DELETE` API endpoint to release the legal hold on an object of the given type with specified ID.  Responds with the hold released.
+#[delete("/hold/<object_type>/<id>")]
+pub fn release_hold(object_type: String, id: String, yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.release_hold(&object_type, &id) {
+        Ok(hold) => Ok(content::Json(serde_json::to_string_pretty(&hold).unwrap())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `GET` API endpoint to list the deletions scheduled and not yet run.
+#[get("/schedules")]
+pub fn list_schedules(yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.scheduled_deletions() {
+        Ok(scheduled) => Ok(content::Json(serde_json::to_string_pretty(&scheduled).unwrap())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `POST` API endpoint to schedule the deletion of an object of the given type with specified ID for the time `at`, in seconds since the unix epoch.  Scheduling an instance again moves its deletion to the new time.  Responds with the scheduled deletion.
+#[post("/schedule/<object_type>/<id>?<at>")]
+pub fn schedule_deletion(object_type: String, id: String, at: u64, yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.schedule_deletion(&object_type, &id, at) {
+        Ok(scheduled) => Ok(content::Json(serde_json::to_string_pretty(&scheduled).unwrap())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// `DELETE` API endpoint to cancel the scheduled deletion of an object of the given type with specified ID.  Responds with the deletion cancelled.
+#[delete("/schedule/<object_type>/<id>")]
+pub fn cancel_deletion(object_type: String, id: String, yamls: State<DelfYamls>) -> Result<content::Json<String>, Status> {
+    let graph = match DelfGraph::new(&yamls) {
+        Ok(graph) => graph,
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    match graph.cancel_deletion(&object_type, &id) {
+        Ok(scheduled) => Ok(content::Json(serde_json::to_string_pretty(&scheduled).unwrap())),
+        Err(_) => Err(Status::NotFound),
     }
-    graph.delete_edge(&edge_type, &from_id, &to_id);
-    Status::Ok
 }
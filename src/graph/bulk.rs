@@ -0,0 +1,182 @@
+use serde::Serialize;
+
+use super::cascade::Cascade;
+use super::hold::HeldInstance;
+use super::plan::DeletionPlan;
+use super::predicate::Predicate;
+use super::DelfGraph;
+
+// the number of ids selected from the storage at a time
+const PAGE_SIZE: usize = 100;
+
+/// What a deletion of the instances matching a predicate removed
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BulkDeletion {
+    /// The ids of the instances matching the predicate, each deleted with its cascade
+    pub ids: Vec<String>,
+    /// The ids to restore the deletion of each instance with, if a restoration log is configured
+    pub deletion_ids: Vec<String>,
+    /// The instances skipped because they are under a legal hold, across all the deletions
+    pub held: Vec<HeldInstance>,
+}
+
+impl DelfGraph {
+    /// The ids of the instances of the object matching the predicate, read from the storage a page at a time.  Soft deleted instances don't match, and a predicate on columns the object's table doesn't have is an error.
+    pub fn matching_ids(&self, object_name: &String, predicate: &Predicate) -> Result<Vec<String>, String> {
+        self.check_filter(object_name, predicate)?;
+        let mut ids: Vec<String> = Vec::new();
+        loop {
+            let page = self.matching_page(object_name, predicate, ids.last());
+            let done = page.len() < PAGE_SIZE;
+            ids.extend(page);
+            if done {
+                return Ok(ids);
+            }
+        }
+    }
+
+    /// Plan the deletion of every instance of the object matching the predicate without deleting anything.  Instances reached from several of them are only planned once.
+    pub fn plan_delete_where(&self, object_name: &String, predicate: &Predicate) -> Result<DeletionPlan, String> {
+        let ids = self.matching_ids(object_name, predicate)?;
+        let mut deletion_plan = DeletionPlan::default();
+        let mut cascade = Cascade::new(self, true)?;
+        for id in ids.iter() {
+            if let Some(obj) = cascade.delete_object(object_name, id, None) {
+                deletion_plan.objects.push(obj);
+            }
+        }
+        deletion_plan.held = cascade.held();
+        return Ok(deletion_plan);
+    }
+
+    /// Delete every instance of the object matching the predicate, each with its cascade, selecting them from the storage a page at a time.  If limits are configured, the deletions are planned together first, and refused with an error if the plan exceeds them.  A predicate on columns the object's table doesn't have is refused before anything is planned or deleted.
+    pub fn delete_where(&self, object_name: &String, predicate: &Predicate) -> Result<BulkDeletion, String> {
+        self.check_filter(object_name, predicate)?;
+        if self.limits.is_some() {
+            self.check_limits(&self.plan_delete_where(object_name, predicate)?)?;
+        }
+
+        let mut bulk = BulkDeletion::default();
+        let mut after: Option<String> = None;
+        loop {
+            let page = self.matching_page(object_name, predicate, after.as_ref());
+            for id in page.iter() {
+                let deletion = self.run_delete_object(object_name, id)?;
+                bulk.ids.push(id.clone());
+                if let Some(deletion_id) = deletion.deletion_id {
+                    bulk.deletion_ids.push(deletion_id);
+                }
+                for held in deletion.held.into_iter() {
+                    if !bulk.held.contains(&held) {
+                        bulk.held.push(held);
+                    }
+                }
+            }
+            // instances which weren't deleted, such as held ones, still match, so the next page starts after this one
+            if page.len() < PAGE_SIZE {
+                return Ok(bulk);
+            }
+            after = page.last().cloned();
+        }
+    }
+
+    // Check the object exists and has every column the predicate refers to
+    fn check_filter(&self, object_name: &String, predicate: &Predicate) -> Result<(), String> {
+        if !self.nodes.contains_key(object_name) {
+            return Err(format!("Unknown object type: {}", object_name));
+        }
+        let obj = self.get_object(object_name);
+        let columns = self.storages.get(&obj.storage).unwrap().columns(&obj.name);
+        let missing: Vec<String> = predicate
+            .columns()
+            .into_iter()
+            .filter(|c| !columns.contains(c))
+            .collect();
+        if missing.len() > 0 {
+            return Err(format!(
+                "The filter refers to columns not in {}: {}",
+                obj.name,
+                missing.join(", ")
+            ));
+        }
+        return Ok(());
+    }
+
+    // Get the page of ids of the instances matching the predicate after the id given
+    fn matching_page(&self, object_name: &String, predicate: &Predicate, after: Option<&String>) -> Vec<String> {
+        let obj = self.get_object(object_name);
+        let predicate = match obj.live_predicate() {
+            Some(live) => Predicate::and(Some(predicate), live),
+            None => predicate.clone(),
+        };
+        return self.storages.get(&obj.storage).unwrap().get_object_ids_page(
+            &obj.name,
+            &obj.id_field,
+            &obj.id_type,
+            &predicate,
+            after,
+            PAGE_SIZE,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deletes_every_matching_instance_in_pages() {
+        let schema = "
+object_type:
+  name: user
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: user_post
+      to:
+        object_type: post
+        field: user_id
+      deletion: deep
+---
+object_type:
+  name: post
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let config = "
+storages: []
+limits:
+  max_instances: 260
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, config);
+        for i in 1..=250 {
+            let id = i.to_string();
+            let flag = if i % 2 == 0 { "spam" } else { "ok" };
+            memory.insert("user", &[("id", &id), ("flagged", flag)]);
+            if i % 2 == 0 {
+                memory.insert("post", &[("id", &(1000 + i).to_string()), ("user_id", &id)]);
+            }
+        }
+
+        let user = String::from("user");
+        let everyone = Predicate::parse("id > 0").unwrap();
+        assert!(graph.delete_where(&user, &everyone).is_err());
+        assert_eq!(memory.column("user", "id").len(), 250);
+
+        let unknown = Predicate::parse("flaged = 'spam'").unwrap();
+        assert!(graph.matching_ids(&user, &unknown).is_err());
+        assert!(graph.plan_delete_where(&user, &unknown).is_err());
+        assert!(graph.delete_where(&user, &unknown).is_err());
+        assert_eq!(memory.column("user", "id").len(), 250);
+
+        let spam = Predicate::parse("flagged = 'spam'").unwrap();
+        assert_eq!(graph.matching_ids(&user, &spam).unwrap().len(), 125);
+        let bulk = graph.delete_where(&user, &spam).unwrap();
+        assert_eq!(bulk.ids.len(), 125);
+        assert_eq!(memory.column("user", "id").len(), 125);
+        assert_eq!(memory.column("post", "id").len(), 0);
+    }
+}
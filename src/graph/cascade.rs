@@ -0,0 +1,1550 @@
+use std::collections::{BTreeSet, HashSet};
+use std::sync::{Arc, Mutex};
+
+use petgraph::Outgoing;
+
+use super::checkpoint::{Checkpoint, Task};
+use super::edge::{self, DelfEdge, DeleteType};
+use super::hold::HeldInstance;
+use super::object::{self, DelfObject};
+use super::parallel::WorkPool;
+use super::plan::{PlannedEdge, PlannedObject};
+use super::predicate::Predicate;
+use super::restore::{Change, RestorationRecord};
+use super::{DelfGraph, Deletion};
+use crate::storage::{DelfStorageConnection, Row};
+use crate::store;
+
+/// The state of a cascade, shared by every thread working on it
+#[derive(Default)]
+pub(crate) struct CascadeState {
+    // the unfinished tasks of the cascade, if it's being checkpointed
+    checkpoint: Option<Checkpoint>,
+    // the id the deletion is logged under, if it's being logged
+    deletion_id: Option<String>,
+    // the number of changes logged so far
+    sequence: usize,
+    // (object name, id) of instances deleted so far
+    visited_objects: HashSet<(String, String)>,
+    // (edge name, from id, to id) of edge instances handled so far, with no to id when all edges from the instance are
+    visited_edges: HashSet<(String, String, Option<String>)>,
+    // (edge name, from id) of edges deleted so far in a dry run
+    deleted_from: HashSet<(String, String)>,
+    // (edge name, to id) of edges deleted so far in a dry run
+    deleted_to: HashSet<(String, String)>,
+    // refcount checks which found another reference while other threads were deleting, to be checked again once they're done
+    pub(crate) rechecks: Vec<Task>,
+    // removals of instances in child-first storages, put off until the rest of a parallel cascade is done
+    pub(crate) deferred: Vec<Task>,
+    // (object name, id) of the instances under a legal hold
+    held: Arc<HashSet<(String, String)>>,
+    // the held instances the cascade reached, and skipped
+    skipped: BTreeSet<HeldInstance>,
+    // the error which stopped the cascade, such as a checkpoint which couldn't be written
+    failure: Option<String>,
+}
+
+impl CascadeState {
+    /// The state of a new cascade, whose checkpoint is kept under the id if given
+    pub(crate) fn new(graph: &DelfGraph, dry_run: bool, checkpoint_id: Option<&String>) -> Result<CascadeState, String> {
+        let held = load_held(graph)?;
+        let deletion_id = match &graph.restoration {
+            Some(log) if !dry_run => {
+                if let Err(e) = log.prune() {
+                    eprintln!("{}", e);
+                }
+                Some(store::new_id())
+            }
+            _ => None,
+        };
+
+        let checkpoint = match &graph.checkpoints {
+            Some(_) if !dry_run => Some(Checkpoint {
+                id: checkpoint_id.cloned().unwrap_or_else(store::new_id),
+                deletion_id: deletion_id.clone(),
+                ..Checkpoint::default()
+            }),
+            _ => None,
+        };
+
+        Ok(CascadeState {
+            checkpoint,
+            deletion_id,
+            held,
+            ..CascadeState::default()
+        })
+    }
+
+    /// The number of object instances deleted so far
+    pub(crate) fn deleted_count(&self) -> usize {
+        return self.visited_objects.len();
+    }
+}
+
+/// Walks the graph to delete an object or edge instance and everything it cascades to, returning a tree of what was deleted.
+///
+/// The walk uses a work stack rather than recursion, so deep hierarchies can't overflow the stack, and each object and edge instance is handled once, so cycles and symmetric inverse edges can't loop.
+///
+/// In a dry run only reads are made against the storages.  The instances which would have been deleted are tracked, so that later reads (such as `refcount` checks) see the state the storages would be in part way through the real deletion.
+///
+/// Otherwise, if a restoration log is configured, the rows each storage call changes are snapshotted into it under a new deletion id.  If checkpoints are configured, the cascade's unfinished tasks are kept in a checkpoint so it can be resumed if the process stops.
+///
+/// In a parallel cascade, the edges from each deleted object instance are handed to a pool of threads, each with a Cascade of its own sharing the same state.  The tree of what was deleted isn't kept.
+///
+/// Instances in a `child_first` storage are removed only once the edges from them, and everything those cascade to, have been handled, so foreign keys referring to them never block their removal.
+pub(crate) struct Cascade<'a> {
+    graph: &'a DelfGraph,
+    dry_run: bool,
+    state: Arc<Mutex<CascadeState>>,
+    // the pool edges are handed to, in a parallel cascade
+    pool: Option<&'a WorkPool>,
+}
+
+impl<'a> Cascade<'a> {
+    pub(crate) fn new(graph: &'a DelfGraph, dry_run: bool) -> Result<Cascade<'a>, String> {
+        let state = Arc::new(Mutex::new(CascadeState::new(graph, dry_run, None)?));
+        return Ok(Cascade::with_state(graph, dry_run, state, None));
+    }
+
+    /// Create a cascade working on a shared state, handing the edges from deleted instances to the pool if there is one
+    pub(crate) fn with_state(
+        graph: &'a DelfGraph,
+        dry_run: bool,
+        state: Arc<Mutex<CascadeState>>,
+        pool: Option<&'a WorkPool>,
+    ) -> Cascade<'a> {
+        Cascade {
+            graph,
+            dry_run,
+            state,
+            pool,
+        }
+    }
+
+    /// Continue a cascade from its checkpoint, running each of its unfinished tasks.
+    pub(crate) fn resume(graph: &'a DelfGraph, checkpoint: Checkpoint) -> Result<Deletion, String> {
+        let pending = checkpoint.pending.clone();
+        let state = CascadeState {
+            deletion_id: checkpoint.deletion_id.clone(),
+            sequence: checkpoint.sequence,
+            checkpoint: Some(checkpoint),
+            held: load_held(graph)?,
+            ..CascadeState::default()
+        };
+        let mut cascade = Cascade::with_state(graph, false, Arc::new(Mutex::new(state)), None);
+
+        for task in pending.iter() {
+            cascade.execute(task);
+            cascade.failure()?;
+            cascade.pop_tasks(&[task.clone()]);
+        }
+        cascade.finish();
+        return Ok(cascade.deletion());
+    }
+
+    /// Run a cascade starting from the task, keeping it in the checkpoint until it's finished.  If the cascade fails, its checkpoint is left to be resumed.
+    pub(crate) fn run(&mut self, task: Task) -> Result<(), String> {
+        self.push_tasks(&[task.clone()])?;
+        self.execute(&task);
+        self.failure()?;
+        self.pop_tasks(&[task]);
+        self.finish();
+        return Ok(());
+    }
+
+    /// The error which stopped the cascade, if it failed
+    pub(crate) fn failure(&self) -> Result<(), String> {
+        match &self.state.lock().unwrap().failure {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// The id the deletion was logged under, if anything was logged.
+    pub(crate) fn deletion_id(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.sequence == 0 {
+            return None;
+        }
+        return state.deletion_id.clone();
+    }
+
+    /// The held instances the cascade skipped
+    pub(crate) fn held(&self) -> Vec<HeldInstance> {
+        return self.state.lock().unwrap().skipped.iter().cloned().collect();
+    }
+
+    /// What the cascade deleted
+    pub(crate) fn deletion(&self) -> Deletion {
+        Deletion {
+            deletion_id: self.deletion_id(),
+            held: self.held(),
+        }
+    }
+
+    /// Delete an object instance, then all of the edges from it.
+    pub(crate) fn delete_object(
+        &mut self,
+        object_name: &String,
+        id: &String,
+        from_edge: Option<&DelfEdge>,
+    ) -> Option<PlannedObject> {
+        let graph = self.graph;
+        let step = Step::Object {
+            obj: graph.get_object(object_name),
+            id: id.clone(),
+            from_edge: from_edge.map(|e| graph.get_edge(&e.name)),
+            parent: None,
+        };
+        match self.traverse(step) {
+            Some(Planned::Object(obj)) => Some(obj),
+            _ => None,
+        }
+    }
+
+    /// Delete all edges of a given type from an object instance, along with the objects they point to as specified by the edge's deletion type.
+    pub(crate) fn delete_all(
+        &mut self,
+        edge: &DelfEdge,
+        from_id: &String,
+        from_id_type: &String,
+    ) -> Option<PlannedEdge> {
+        let step = Step::EdgeAll {
+            edge: self.graph.get_edge(&edge.name),
+            from_id: from_id.clone(),
+            from_id_type: from_id_type.clone(),
+            parent: None,
+        };
+        match self.traverse(step) {
+            Some(Planned::Edge(edge)) => Some(edge),
+            _ => None,
+        }
+    }
+
+    /// Delete a specific edge between two object instances, along with the object it points to as specified by the edge's deletion type.
+    pub(crate) fn delete_one(
+        &mut self,
+        edge: &DelfEdge,
+        from_id: &String,
+        to_id: &String,
+    ) -> Option<PlannedEdge> {
+        let step = Step::EdgeOne {
+            edge: self.graph.get_edge(&edge.name),
+            from_id: from_id.clone(),
+            to_id: to_id.clone(),
+            parent: None,
+        };
+        match self.traverse(step) {
+            Some(Planned::Edge(edge)) => Some(edge),
+            _ => None,
+        }
+    }
+
+    // Run steps from a work stack until it's empty, starting from the given step, then build the tree of what was deleted
+    fn traverse(&mut self, first: Step<'a>) -> Option<Planned> {
+        let mut stack = vec![first];
+        let mut nodes: Vec<Node> = Vec::new();
+
+        while let Some(step) = stack.pop() {
+            if self.failure().is_err() {
+                return None;
+            }
+            match step {
+                Step::Object {
+                    obj,
+                    id,
+                    from_edge,
+                    parent,
+                } => self.step_object(obj, id, from_edge, parent, &mut stack, &mut nodes),
+                Step::RefCount {
+                    edge,
+                    to_obj,
+                    to_id,
+                    from_id,
+                    parent,
+                } => {
+                    if self.is_last_ref(edge, to_obj, &to_id, &from_id) {
+                        stack.push(Step::Object {
+                            obj: to_obj,
+                            id: to_id,
+                            from_edge: Some(edge),
+                            parent,
+                        });
+                    } else if self.pool.is_some() {
+                        // the other reference may be being deleted by another thread
+                        self.state.lock().unwrap().rechecks.push(Task::RefCount {
+                            edge: edge.name.clone(),
+                            to_id,
+                            from_id,
+                        });
+                    }
+                }
+                Step::EdgeAll {
+                    edge,
+                    from_id,
+                    from_id_type,
+                    parent,
+                } => self.step_edge_all(edge, from_id, from_id_type, parent, &mut stack, &mut nodes),
+                Step::EdgeOne {
+                    edge,
+                    from_id,
+                    to_id,
+                    parent,
+                } => self.step_edge_one(edge, from_id, to_id, parent, &mut stack, &mut nodes),
+                Step::FinishAll { edge, node, parent } => {
+                    let to_obj = self.graph.get_object(&edge.to.object_type);
+                    let from_id = nodes[node].planned_edge().from_id.clone();
+                    let deleted = self.delete_edge(edge, to_obj, &from_id, None);
+                    if deleted && !self.dry_run {
+                        println!("Edges Deleted: {:#?}", edge.name);
+                    }
+                    if deleted || nodes[node].children.len() > 0 || nodes[node].inverses.len() > 0 {
+                        attach(&mut nodes, parent, node);
+                    }
+                }
+                Step::FinishOne { edge, node, parent } => {
+                    let to_obj = self.graph.get_object(&edge.to.object_type);
+                    let from_id = nodes[node].planned_edge().from_id.clone();
+                    let to_id = nodes[node].planned_edge().to_ids[0].clone();
+                    let deleted = self.delete_edge(edge, to_obj, &from_id, Some(&to_id));
+                    if deleted {
+                        if !self.dry_run {
+                            println!("Edge deleted: {:#?}", edge.name);
+                        }
+                        match &edge.inverse {
+                            Some(inverse) => stack.push(Step::EdgeOne {
+                                edge: self.graph.get_edge(inverse),
+                                from_id: to_id,
+                                to_id: from_id,
+                                parent: Some(Parent::Inverse(node)),
+                            }),
+                            None => (),
+                        }
+                    }
+                    if deleted || nodes[node].children.len() > 0 {
+                        attach(&mut nodes, parent, node);
+                    }
+                }
+                Step::Remove {
+                    obj,
+                    id,
+                    from_edge,
+                    task,
+                } => {
+                    self.remove(obj, &id, from_edge);
+                    self.pop_tasks(&[task]);
+                }
+                Step::PopTask(task) => self.pop_tasks(&[task]),
+            }
+        }
+
+        if nodes.len() == 0 || !nodes[0].attached {
+            return None;
+        }
+        return Some(build(nodes));
+    }
+
+    // Delete an object instance, then push the steps to delete the edges from it
+    fn step_object(
+        &mut self,
+        obj: &'a DelfObject,
+        id: String,
+        from_edge: Option<&'a DelfEdge>,
+        parent: Option<Parent>,
+        stack: &mut Vec<Step<'a>>,
+        nodes: &mut Vec<Node>,
+    ) {
+        let graph = self.graph;
+        // soft deleted instances keep what they refer to until they're purged, so restoring them brings everything back
+        let edges: Vec<&'a DelfEdge> = match &obj.soft_delete {
+            Some(_) => Vec::new(),
+            None => graph
+                .graph
+                .edges_directed(graph.nodes[&obj.name], Outgoing)
+                .map(|e| e.weight())
+                .collect(),
+        };
+
+        if !obj.deletable_by(from_edge) {
+            return;
+        }
+
+        // each instance is only deleted once, though it may not be deletable through every edge
+        let key = (obj.name.clone(), id.clone());
+
+        // held instances are skipped, and so is anything reached only through them
+        let held = self.state.lock().unwrap().held.clone();
+        if held.contains(&key) {
+            self.state.lock().unwrap().skipped.insert(HeldInstance {
+                object: obj.name.clone(),
+                id: id.clone(),
+            });
+            return;
+        }
+
+        let deleted = if self.dry_run {
+            !self.state.lock().unwrap().visited_objects.contains(&key)
+                && self.storage(obj).has_object(obj, &id)
+                && self.state.lock().unwrap().visited_objects.insert(key)
+        } else if graph.is_child_first(obj) || edges.iter().any(|e| e.from.is_some()) {
+            if !self.state.lock().unwrap().visited_objects.insert(key)
+                || !self.storage(obj).has_object(obj, &id)
+            {
+                return;
+            }
+            // the instance is removed last, once everything referring to it is gone, and the edges stored on its row have been read
+            let remove = Task::Remove {
+                object: obj.name.clone(),
+                id: id.clone(),
+                from_edge: from_edge.map(|e| e.name.clone()),
+            };
+            let mut tasks = edge_tasks(&edges, &id);
+            tasks.push(remove.clone());
+            if self.push_tasks(&tasks).is_err() {
+                return;
+            }
+            match self.pool {
+                Some(_) => self.state.lock().unwrap().deferred.push(remove),
+                None => stack.push(Step::Remove {
+                    obj,
+                    id: id.clone(),
+                    from_edge,
+                    task: remove,
+                }),
+            }
+            true
+        } else {
+            if !self.state.lock().unwrap().visited_objects.insert(key) {
+                return;
+            }
+            // the edges from the instance can't be found once it's gone, so they're checkpointed first
+            let tasks = edge_tasks(&edges, &id);
+            if self.push_tasks(&tasks).is_err() {
+                return;
+            }
+            let deleted = self.remove(obj, &id, from_edge);
+            if !deleted {
+                self.pop_tasks(&tasks);
+            }
+            deleted
+        };
+
+        if !deleted {
+            return;
+        }
+
+        let node = nodes.len();
+        nodes.push(Node::new(Planned::Object(PlannedObject {
+            object: obj.name.clone(),
+            id: id.clone(),
+            storage: obj.storage.clone(),
+            edges: Vec::new(),
+        })));
+        attach(nodes, parent, node);
+
+        for (e, task) in edges.iter().zip(edge_tasks(&edges, &id)).rev() {
+            if let Some(pool) = self.pool {
+                let to_obj = graph.get_object(&e.to.object_type);
+                pool.submit(task, &to_obj.storage);
+                continue;
+            }
+            stack.push(Step::PopTask(task));
+            stack.push(Step::EdgeAll {
+                edge: e,
+                from_id: id.clone(),
+                from_id_type: obj.id_type.clone(),
+                parent: Some(Parent::Child(node)),
+            });
+        }
+    }
+
+    // Delete an object instance from its storage, logging the rows changed
+    fn remove(&mut self, obj: &DelfObject, id: &String, from_edge: Option<&DelfEdge>) -> bool {
+        let held = self.state.lock().unwrap().held.clone();
+        let rows = self.snapshot(obj, &obj.name, &obj.id_field, &obj.id_type, id, None);
+        let deleted = obj.delete(id, from_edge, &self.graph.storages, &held);
+        if deleted {
+            match &obj.deletion {
+                // anonymized and soft deleted rows are kept, so restoring them puts back each changed field
+                object::DeleteType::Anonymize(fields) => {
+                    for a in fields.iter() {
+                        let change = Change::Field {
+                            id_field: obj.id_field.clone(),
+                            field: a.field.clone(),
+                            rows: rows.clone(),
+                        };
+                        self.log(obj, &obj.name, change);
+                    }
+                }
+                _ => match &obj.soft_delete {
+                    Some(soft_delete) => {
+                        let change = Change::Field {
+                            id_field: obj.id_field.clone(),
+                            field: soft_delete.field.clone(),
+                            rows,
+                        };
+                        self.log(obj, &obj.name, change);
+                    }
+                    None => self.log(obj, &obj.name, Change::Object { rows }),
+                },
+            }
+        }
+        return deleted;
+    }
+
+    /// Purge a soft deleted object instance: delete the edges from it, along with everything they cascade to, then remove its row for good.  Returns whether the row was removed.
+    pub(crate) fn purge(&mut self, obj: &'a DelfObject, id: &String) -> bool {
+        let graph = self.graph;
+        let edges: Vec<&'a DelfEdge> = graph
+            .graph
+            .edges_directed(graph.nodes[&obj.name], Outgoing)
+            .map(|e| e.weight())
+            .collect();
+        let tasks = edge_tasks(&edges, id);
+        if self.push_tasks(&tasks).is_err() {
+            return false;
+        }
+        for (e, task) in edges.iter().zip(tasks.into_iter()) {
+            self.delete_all(e, id, &obj.id_type);
+            self.pop_tasks(&[task]);
+        }
+
+        let rows = self.snapshot(obj, &obj.name, &obj.id_field, &obj.id_type, id, None);
+        let purged = self.storage(obj).delete_object(obj, id);
+        if purged {
+            println!("Object purged: {:#?} {}", obj.name, id);
+            self.log(obj, &obj.name, Change::Object { rows });
+        }
+        return purged;
+    }
+
+    // Find the instances of an edge from an object instance, then push the steps to delete what they point to, their inverses, and then the edges themselves
+    fn step_edge_all(
+        &mut self,
+        edge: &'a DelfEdge,
+        from_id: String,
+        from_id_type: String,
+        parent: Option<Parent>,
+        stack: &mut Vec<Step<'a>>,
+        nodes: &mut Vec<Node>,
+    ) {
+        let key = (edge.name.clone(), from_id.clone(), None);
+        if !self.state.lock().unwrap().visited_edges.insert(key) {
+            return;
+        }
+
+        let graph = self.graph;
+        let to_obj = graph.get_object(&edge.to.object_type);
+
+        // collect object ids the edges point to
+        let to_ids = self.get_object_ids(edge, &from_id, &from_id_type);
+
+        let node = nodes.len();
+        nodes.push(Node::new(Planned::Edge(self.planned_edge(
+            edge,
+            to_obj,
+            &from_id,
+            to_ids.clone(),
+        ))));
+
+        stack.push(Step::FinishAll { edge, node, parent });
+
+        match &edge.inverse {
+            Some(inverse) => {
+                let inverse_edge = graph.get_edge(inverse);
+                for to_id in to_ids.iter().rev() {
+                    stack.push(Step::EdgeOne {
+                        edge: inverse_edge,
+                        from_id: to_id.clone(),
+                        to_id: from_id.clone(),
+                        parent: Some(Parent::Inverse(node)),
+                    });
+                }
+            }
+            None => (),
+        }
+
+        for to_id in to_ids.into_iter().rev() {
+            match to_step(edge, to_obj, &from_id, to_id, Parent::Child(node)) {
+                Some(step) => stack.push(step),
+                None => (),
+            }
+        }
+    }
+
+    // Push the steps to delete what a specific edge instance points to, and then the edge itself
+    fn step_edge_one(
+        &mut self,
+        edge: &'a DelfEdge,
+        from_id: String,
+        to_id: String,
+        parent: Option<Parent>,
+        stack: &mut Vec<Step<'a>>,
+        nodes: &mut Vec<Node>,
+    ) {
+        let key = (edge.name.clone(), from_id.clone(), Some(to_id.clone()));
+        if !self.state.lock().unwrap().visited_edges.insert(key) {
+            return;
+        }
+
+        let to_obj = self.graph.get_object(&edge.to.object_type);
+
+        let node = nodes.len();
+        nodes.push(Node::new(Planned::Edge(self.planned_edge(
+            edge,
+            to_obj,
+            &from_id,
+            vec![to_id.clone()],
+        ))));
+
+        stack.push(Step::FinishOne { edge, node, parent });
+        match to_step(edge, to_obj, &from_id, to_id, Parent::Child(node)) {
+            Some(step) => stack.push(step),
+            None => (),
+        }
+    }
+
+    // Check whether any edge other than the one from `from_id` still refers to the object instance
+    fn is_last_ref(&self, edge: &DelfEdge, to_obj: &DelfObject, to_id: &String, from_id: &String) -> bool {
+        let s = self.storage(to_obj);
+        for inbound_edge in self.graph.get_inbound_edges(to_obj).iter() {
+            if inbound_edge.to.mapping_table.is_some()
+                || inbound_edge.from.is_some()
+                || inbound_edge.to.path.is_some()
+            {
+                // a mapping table, the rows of the objects edges start from, or a path of join tables, can hold the same edge from other instances too
+                let sources = self.sources(inbound_edge, to_obj, to_id);
+                let state = self.state.lock().unwrap();
+                let other = sources.into_iter().any(|source| {
+                    !(*inbound_edge == edge && source == *from_id)
+                        && !(self.dry_run
+                            && state.deleted_from.contains(&(inbound_edge.name.clone(), source)))
+                });
+                if other {
+                    return false;
+                }
+            } else if *inbound_edge != edge {
+                let key = (inbound_edge.name.clone(), to_id.clone());
+                if self.dry_run && self.state.lock().unwrap().deleted_to.contains(&key) {
+                    continue;
+                }
+                if s.has_edge(to_obj, to_id, inbound_edge) {
+                    return false;
+                }
+            }
+        }
+
+        return true;
+    }
+
+    // Get the ids of the instances a mapping table edge, an edge stored on its source, or a path edge, refers to the object instance from
+    fn sources(&self, edge: &DelfEdge, to_obj: &DelfObject, to_id: &String) -> Vec<String> {
+        let from_obj = self.graph.get_edge_source(edge);
+        if let Some(hops) = &edge.to.path {
+            let mut ids = vec![to_id.clone()];
+            for (i, hop) in hops.iter().enumerate().rev() {
+                let (from_type, to_type) = self.hop_types(edge, i);
+                ids = self.follow_hop(edge, i, &ids, &to_type, &hop.to, &hop.from, &from_type);
+            }
+            return ids;
+        }
+        if let Some(from) = &edge.from {
+            if from.format.is_some() {
+                let predicate = Predicate::and(edge.row_predicate().as_ref(), edge.points_to(to_obj, to_id));
+                return self.storage(from_obj).get_object_ids_matching(
+                    &from.object_type,
+                    &from_obj.id_field,
+                    &from_obj.id_type,
+                    &predicate,
+                );
+            }
+            return self.storage(from_obj).get_object_ids(
+                to_id,
+                &to_obj.id_type,
+                &from.field,
+                &from.object_type,
+                &from_obj.id_field,
+                &from_obj.id_type,
+                edge.row_predicate().as_ref(),
+            );
+        }
+        return self.storage(to_obj).get_object_ids(
+            to_id,
+            &to_obj.id_type,
+            &to_obj.id_field,
+            edge.table(),
+            &edge.to.field,
+            &from_obj.id_type,
+            edge.row_predicate().as_ref(),
+        );
+    }
+
+    // Delete the instances of the edge from the object instance, or only the one to `to_id` if given, in a dry run only recording what would be deleted
+    fn delete_edge(
+        &mut self,
+        edge: &DelfEdge,
+        to_obj: &DelfObject,
+        from_id: &String,
+        to_id: Option<&String>,
+    ) -> bool {
+        let from_obj = self.graph.get_edge_source(edge);
+
+        if !self.dry_run {
+            let predicate = match to_id {
+                Some(id) => Some(Predicate::and(
+                    edge.row_predicate().as_ref(),
+                    edge.points_to(to_obj, id),
+                )),
+                None => edge.row_predicate(),
+            };
+            if let Some(hops) = &edge.to.path {
+                // the rows of the first join table are deleted, only those leading to `to_id` if given
+                let first = &hops[0];
+                let keys = match to_id {
+                    Some(id) => {
+                        let (_, key_type) = self.hop_types(edge, 0);
+                        self.follow_hop(edge, 0, &vec![from_id.clone()], &from_obj.id_type, &first.from, &first.to, &key_type)
+                            .into_iter()
+                            .filter(|key| self.follow_path(edge, 1, vec![key.clone()]).contains(id))
+                            .map(Some)
+                            .collect()
+                    }
+                    None => vec![None],
+                };
+                let mut deleted = false;
+                for key in keys.iter() {
+                    let predicate = match key {
+                        Some(key) => Some(Predicate::and(
+                            edge.row_predicate().as_ref(),
+                            Predicate::equals(&first.to, key),
+                        )),
+                        None => edge.row_predicate(),
+                    };
+                    let rows = self.snapshot(
+                        to_obj,
+                        &first.table,
+                        &first.from,
+                        &from_obj.id_type,
+                        from_id,
+                        predicate.as_ref(),
+                    );
+                    if self.storage(to_obj).delete_edge(from_obj, to_obj, from_id, key.as_ref(), edge) {
+                        self.log(to_obj, &first.table, Change::Mapping { rows });
+                        deleted = true;
+                    }
+                }
+                return deleted;
+            }
+
+            if let Some(from) = &edge.from {
+                let rows = self.snapshot(
+                    from_obj,
+                    &from.object_type,
+                    &from_obj.id_field,
+                    &from_obj.id_type,
+                    from_id,
+                    predicate.as_ref(),
+                );
+                let change = Change::Field {
+                    id_field: from_obj.id_field.clone(),
+                    field: from.field.clone(),
+                    rows,
+                };
+                let deleted = self.storage(from_obj).delete_edge(from_obj, to_obj, from_id, to_id, edge);
+                if deleted {
+                    self.log(from_obj, &from.object_type, change);
+                }
+                return deleted;
+            }
+
+            let (table, change) = match &edge.to.mapping_table {
+                Some(table) => {
+                    let rows = self.snapshot(
+                        to_obj,
+                        table,
+                        &edge.to.field,
+                        &from_obj.id_type,
+                        from_id,
+                        predicate.as_ref(),
+                    );
+                    (table, Change::Mapping { rows })
+                }
+                None => {
+                    let rows = self.snapshot(
+                        to_obj,
+                        &to_obj.name,
+                        &edge.to.field,
+                        &from_obj.id_type,
+                        from_id,
+                        predicate.as_ref(),
+                    );
+                    let change = Change::Field {
+                        id_field: to_obj.id_field.clone(),
+                        field: edge.to.field.clone(),
+                        rows,
+                    };
+                    (&to_obj.name, change)
+                }
+            };
+            let deleted = self.storage(to_obj).delete_edge(from_obj, to_obj, from_id, to_id, edge);
+            if deleted {
+                self.log(to_obj, table, change);
+            }
+            return deleted;
+        }
+
+        let to_ids: Vec<String> = self
+            .get_object_ids(edge, from_id, &from_obj.id_type)
+            .into_iter()
+            .filter(|id| to_id.map_or(true, |to_id| id == to_id))
+            .collect();
+        let mut state = self.state.lock().unwrap();
+        if to_id.is_none() {
+            state.deleted_from.insert((edge.name.clone(), from_id.clone()));
+        }
+        for id in to_ids.iter() {
+            state.deleted_to.insert((edge.name.clone(), id.clone()));
+        }
+
+        return to_ids.len() > 0;
+    }
+
+    // Get the ids of the objects the edges from the object instance point to
+    pub(super) fn get_object_ids(
+        &self,
+        edge: &DelfEdge,
+        from_id: &String,
+        from_id_type: &String,
+    ) -> Vec<String> {
+        let key = (edge.name.clone(), from_id.clone());
+        if self.dry_run && self.state.lock().unwrap().deleted_from.contains(&key) {
+            return Vec::new();
+        }
+
+        let to_obj = self.graph.get_object(&edge.to.object_type);
+        if let Some(from) = &edge.from {
+            let from_obj = self.graph.get_edge_source(edge);
+            if let Some(format) = from.format {
+                // the ids are read from the list on the source's row
+                let rows = self.storage(from_obj).get_rows(
+                    &from.object_type,
+                    &from_obj.id_field,
+                    from_id_type,
+                    from_id,
+                    edge.row_predicate().as_ref(),
+                );
+                let ids = rows
+                    .iter()
+                    .filter_map(|row| row.get(&from.field))
+                    .flat_map(|value| format.ids(value))
+                    .collect();
+                return self.live_ids(to_obj, ids);
+            }
+
+            // the id is read from the source's row, which no longer refers to anything once cleared
+            let linked = Predicate::not_equals(&from.field, &edge::cleared_id(&to_obj.id_type));
+            let ids = self.storage(from_obj).get_object_ids(
+                from_id,
+                from_id_type,
+                &from_obj.id_field,
+                &from.object_type,
+                &from.field,
+                &to_obj.id_type,
+                Some(&Predicate::and(edge.row_predicate().as_ref(), linked)),
+            );
+            return self.live_ids(to_obj, ids);
+        }
+
+        if edge.to.path.is_some() {
+            let ids = self.follow_path(edge, 0, vec![from_id.clone()]);
+            return self.live_ids(to_obj, ids);
+        }
+
+        // soft deleted instances are ignored when the edge is a field on their rows
+        let (table, predicate) = match (&edge.to.mapping_table, to_obj.live_predicate()) {
+            (Some(tbl), _) => (tbl, edge.row_predicate()),
+            (None, Some(live)) => (&to_obj.name, Some(Predicate::and(edge.row_predicate().as_ref(), live))),
+            (None, None) => (&to_obj.name, edge.row_predicate()),
+        };
+
+        let ids = self.storage(to_obj).get_object_ids(
+            from_id,
+            from_id_type,
+            &edge.to.field,
+            table,
+            &to_obj.id_field,
+            &to_obj.id_type,
+            predicate.as_ref(),
+        );
+        return match edge.to.mapping_table {
+            Some(_) => self.live_ids(to_obj, ids),
+            None => ids,
+        };
+    }
+
+    // Drop the ids of soft deleted instances of the object, for edges which don't read the object's own rows
+    fn live_ids(&self, to_obj: &DelfObject, ids: Vec<String>) -> Vec<String> {
+        let live = match to_obj.live_predicate() {
+            Some(live) => live,
+            None => return ids,
+        };
+        if ids.len() == 0 {
+            return ids;
+        }
+
+        let predicate = Predicate::and(Some(&Predicate::one_of(&to_obj.id_field, &to_obj.id_type, &ids)), live);
+        let found = self.storage(to_obj).get_object_ids_matching(
+            &to_obj.name,
+            &to_obj.id_field,
+            &to_obj.id_type,
+            &predicate,
+        );
+        return ids.into_iter().filter(|id| found.contains(id)).collect();
+    }
+
+    // Follow the join tables of a path edge from its table `start`, given the ids in that table's `from` column, to the ids of the object the edge points to
+    fn follow_path(&self, edge: &DelfEdge, start: usize, ids: Vec<String>) -> Vec<String> {
+        let hops = edge.to.path.as_ref().unwrap();
+        let mut ids = ids;
+        for (i, hop) in hops.iter().enumerate().skip(start) {
+            let (from_type, to_type) = self.hop_types(edge, i);
+            ids = self.follow_hop(edge, i, &ids, &from_type, &hop.from, &hop.to, &to_type);
+        }
+        return ids;
+    }
+
+    // Get the distinct values of the `to_field` of the rows of a path edge's join table with the ids in their `from_field`, in one query
+    fn follow_hop(
+        &self,
+        edge: &DelfEdge,
+        i: usize,
+        ids: &Vec<String>,
+        from_type: &String,
+        from_field: &String,
+        to_field: &String,
+        to_type: &String,
+    ) -> Vec<String> {
+        if ids.len() == 0 {
+            return Vec::new();
+        }
+        let hop = &edge.to.path.as_ref().unwrap()[i];
+        let to_obj = self.graph.get_object(&edge.to.object_type);
+        // the edge's predicate is on the first table, where the edge is stored
+        let row_predicate = if i == 0 { edge.row_predicate() } else { None };
+        let predicate = Predicate::and(row_predicate.as_ref(), Predicate::one_of(from_field, from_type, ids));
+
+        let mut found = Vec::new();
+        for next in self
+            .storage(to_obj)
+            .get_object_ids_matching(&hop.table, to_field, to_type, &predicate)
+        {
+            if !found.contains(&next) {
+                found.push(next);
+            }
+        }
+        return found;
+    }
+
+    // The types of the ids in the `from` and `to` columns of a path edge's join table
+    fn hop_types(&self, edge: &DelfEdge, i: usize) -> (String, String) {
+        let hops = edge.to.path.as_ref().unwrap();
+        let from_type = match i {
+            0 => &self.graph.get_edge_source(edge).id_type,
+            _ => &hops[i - 1].id_type,
+        };
+        let to_type = if i == hops.len() - 1 {
+            &self.graph.get_object(&edge.to.object_type).id_type
+        } else {
+            &hops[i].id_type
+        };
+        return (from_type.clone(), to_type.clone());
+    }
+
+    fn planned_edge(
+        &self,
+        edge: &DelfEdge,
+        to_obj: &DelfObject,
+        from_id: &String,
+        to_ids: Vec<String>,
+    ) -> PlannedEdge {
+        let storage = match &edge.from {
+            Some(_) => &self.graph.get_edge_source(edge).storage,
+            None => &to_obj.storage,
+        };
+        PlannedEdge {
+            edge: edge.name.clone(),
+            storage: storage.clone(),
+            from_id: from_id.clone(),
+            to_ids,
+            objects: Vec::new(),
+            inverses: Vec::new(),
+        }
+    }
+
+    /// Run a task of the cascade
+    pub(crate) fn execute(&mut self, task: &Task) {
+        let graph = self.graph;
+        match task {
+            Task::Object { object, id } if graph.nodes.contains_key(object) => {
+                self.delete_object(object, id, None);
+            }
+            Task::EdgeAll { edge, from_id } if graph.edges.contains_key(edge) => {
+                let e = graph.get_edge(edge);
+                let from_obj = graph.get_edge_source(e);
+                self.delete_all(e, from_id, &from_obj.id_type);
+            }
+            Task::EdgeOne {
+                edge,
+                from_id,
+                to_id,
+            } if graph.edges.contains_key(edge) => {
+                self.delete_one(graph.get_edge(edge), from_id, to_id);
+            }
+            Task::Remove {
+                object,
+                id,
+                from_edge,
+            } if graph.nodes.contains_key(object)
+                && from_edge.iter().all(|e| graph.edges.contains_key(e)) =>
+            {
+                let from_edge = from_edge.as_ref().map(|e| graph.get_edge(e));
+                self.remove(graph.get_object(object), id, from_edge);
+            }
+            Task::Purge { object, id } if graph.nodes.contains_key(object) => {
+                self.purge(graph.get_object(object), id);
+            }
+            Task::RefCount {
+                edge,
+                to_id,
+                from_id,
+            } if graph.edges.contains_key(edge) => {
+                let e = graph.get_edge(edge);
+                let step = Step::RefCount {
+                    edge: e,
+                    to_obj: graph.get_object(&e.to.object_type),
+                    to_id: to_id.clone(),
+                    from_id: from_id.clone(),
+                    parent: None,
+                };
+                self.traverse(step);
+            }
+            _ => eprintln!("Skipping task for a type no longer in the schema: {:?}", task),
+        }
+    }
+
+    /// Add unfinished tasks to the checkpoint.  If it can't be written the cascade fails, so nothing is deleted which the checkpoint doesn't lead back to.
+    pub(crate) fn push_tasks(&mut self, tasks: &[Task]) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(checkpoint) = &mut state.checkpoint {
+            checkpoint.pending.extend(tasks.iter().cloned());
+        }
+        let saved = self.save_checkpoint(&mut state);
+        if let Err(e) = &saved {
+            state.failure.get_or_insert(e.clone());
+        }
+        return saved;
+    }
+
+    /// Remove finished tasks from the checkpoint
+    pub(crate) fn pop_tasks(&mut self, tasks: &[Task]) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(checkpoint) = &mut state.checkpoint {
+            for task in tasks.iter() {
+                if let Some(i) = checkpoint.pending.iter().position(|t| t == task) {
+                    checkpoint.pending.remove(i);
+                }
+            }
+        }
+        // a checkpoint left with finished tasks only runs them again when resumed
+        if let Err(e) = self.save_checkpoint(&mut state) {
+            eprintln!("{}", e);
+        }
+    }
+
+    fn save_checkpoint(&self, state: &mut CascadeState) -> Result<(), String> {
+        let sequence = state.sequence;
+        if let (Some(checkpoint), Some(config)) = (&mut state.checkpoint, &self.graph.checkpoints) {
+            checkpoint.sequence = sequence;
+            return checkpoint.save(config);
+        }
+        return Ok(());
+    }
+
+    /// Remove the checkpoint once the cascade is finished
+    pub(crate) fn finish(&mut self) {
+        let state = self.state.lock().unwrap();
+        if let (Some(checkpoint), Some(config)) = (&state.checkpoint, &self.graph.checkpoints) {
+            if let Err(e) = checkpoint.remove(config) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    // Get the rows about to be changed, if the deletion is being logged
+    fn snapshot(
+        &self,
+        obj: &DelfObject,
+        table: &String,
+        field: &String,
+        value_type: &String,
+        value: &String,
+        predicate: Option<&Predicate>,
+    ) -> Vec<Row> {
+        if self.state.lock().unwrap().deletion_id.is_none() {
+            return Vec::new();
+        }
+        return self.storage(obj).get_rows(table, field, value_type, value, predicate);
+    }
+
+    // Add the changed rows to the restoration log
+    fn log(&mut self, obj: &DelfObject, table: &String, change: Change) {
+        let mut state = self.state.lock().unwrap();
+        let (log, deletion_id) = match (&self.graph.restoration, &state.deletion_id) {
+            (Some(log), Some(deletion_id)) => (log, deletion_id.clone()),
+            _ => return,
+        };
+        let empty = match &change {
+            Change::Object { rows } | Change::Mapping { rows } => rows.len() == 0,
+            Change::Field { rows, .. } => rows.len() == 0,
+        };
+        if empty {
+            return;
+        }
+
+        let record = RestorationRecord {
+            deletion_id,
+            sequence: state.sequence,
+            timestamp: store::now(),
+            storage: obj.storage.clone(),
+            table: table.clone(),
+            change,
+        };
+        match log.append(&record) {
+            Ok(_) => state.sequence += 1,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    fn storage(&self, obj: &DelfObject) -> &'a dyn DelfStorageConnection {
+        return &**(self.graph.storages.get(&obj.storage).unwrap());
+    }
+}
+
+// Load the instances under a legal hold.  A deletion can't safely go ahead without knowing them, so it is refused if they can't be read.
+fn load_held(graph: &DelfGraph) -> Result<Arc<HashSet<(String, String)>>, String> {
+    match &graph.holds {
+        Some(config) => match config.held() {
+            Ok(held) => Ok(Arc::new(held)),
+            Err(e) => Err(format!("couldn't read the legal holds: {}", e)),
+        },
+        None => Ok(Arc::new(HashSet::new())),
+    }
+}
+
+// A step of the walk, run from the top of the work stack
+enum Step<'a> {
+    // delete an object instance, then push the steps for the edges from it
+    Object {
+        obj: &'a DelfObject,
+        id: String,
+        from_edge: Option<&'a DelfEdge>,
+        parent: Option<Parent>,
+    },
+    // delete the object instance an edge from `from_id` points to, if no other edge refers to it
+    RefCount {
+        edge: &'a DelfEdge,
+        to_obj: &'a DelfObject,
+        to_id: String,
+        from_id: String,
+        parent: Option<Parent>,
+    },
+    // push the steps to delete all edges of a type from an object instance
+    EdgeAll {
+        edge: &'a DelfEdge,
+        from_id: String,
+        from_id_type: String,
+        parent: Option<Parent>,
+    },
+    // push the steps to delete a specific edge instance
+    EdgeOne {
+        edge: &'a DelfEdge,
+        from_id: String,
+        to_id: String,
+        parent: Option<Parent>,
+    },
+    // delete the edge instances of an `EdgeAll` once what they point to has been handled
+    FinishAll {
+        edge: &'a DelfEdge,
+        node: usize,
+        parent: Option<Parent>,
+    },
+    // delete the edge instance of an `EdgeOne` once what it points to has been handled
+    FinishOne {
+        edge: &'a DelfEdge,
+        node: usize,
+        parent: Option<Parent>,
+    },
+    // delete an object instance in a child-first storage, once the edges from it have been handled
+    Remove {
+        obj: &'a DelfObject,
+        id: String,
+        from_edge: Option<&'a DelfEdge>,
+        task: Task,
+    },
+    // remove a finished task from the checkpoint
+    PopTask(Task),
+}
+
+// Where a node of the tree of deleted instances goes in its parent node
+#[derive(Clone, Copy)]
+enum Parent {
+    // an object deleted through an edge, or an edge deleted from an object
+    Child(usize),
+    // an inverse deleted along with an edge
+    Inverse(usize),
+}
+
+enum Planned {
+    Object(PlannedObject),
+    Edge(PlannedEdge),
+}
+
+// A node of the tree of deleted instances, linked to its children by their index
+struct Node {
+    planned: Planned,
+    attached: bool,
+    children: Vec<usize>,
+    inverses: Vec<usize>,
+}
+
+impl Node {
+    fn new(planned: Planned) -> Node {
+        Node {
+            planned,
+            attached: false,
+            children: Vec::new(),
+            inverses: Vec::new(),
+        }
+    }
+
+    fn planned_edge(&self) -> &PlannedEdge {
+        match &self.planned {
+            Planned::Edge(edge) => edge,
+            Planned::Object(_) => panic!("expected an edge node"),
+        }
+    }
+}
+
+// Add a node to the tree under its parent
+fn attach(nodes: &mut Vec<Node>, parent: Option<Parent>, node: usize) {
+    nodes[node].attached = true;
+    match parent {
+        Some(Parent::Child(p)) => nodes[p].children.push(node),
+        Some(Parent::Inverse(p)) => nodes[p].inverses.push(node),
+        None => (),
+    }
+}
+
+// Build the tree from the root node.  Children are always created after their parents, so building from the last node back means every child is built before its parent.
+fn build(nodes: Vec<Node>) -> Planned {
+    let mut built: Vec<Option<Planned>> = nodes.iter().map(|_| None).collect();
+    for (i, node) in nodes.into_iter().enumerate().rev() {
+        let mut planned = node.planned;
+        match &mut planned {
+            Planned::Object(obj) => {
+                for c in node.children.iter() {
+                    if let Some(Planned::Edge(edge)) = built[*c].take() {
+                        obj.edges.push(edge);
+                    }
+                }
+            }
+            Planned::Edge(edge) => {
+                for c in node.children.iter() {
+                    if let Some(Planned::Object(obj)) = built[*c].take() {
+                        edge.objects.push(obj);
+                    }
+                }
+                for c in node.inverses.iter() {
+                    if let Some(Planned::Edge(inverse)) = built[*c].take() {
+                        edge.inverses.push(inverse);
+                    }
+                }
+            }
+        }
+        built[i] = Some(planned);
+    }
+    return built[0].take().unwrap();
+}
+
+// The step to delete the object instance an edge points to, as specified by the edge's deletion type
+fn to_step<'a>(
+    edge: &'a DelfEdge,
+    to_obj: &'a DelfObject,
+    from_id: &String,
+    to_id: String,
+    parent: Parent,
+) -> Option<Step<'a>> {
+    match edge.deletion {
+        DeleteType::Deep => Some(Step::Object {
+            obj: to_obj,
+            id: to_id,
+            from_edge: Some(edge),
+            parent: Some(parent),
+        }),
+        DeleteType::RefCount => Some(Step::RefCount {
+            edge,
+            to_obj,
+            to_id,
+            from_id: from_id.clone(),
+            parent: Some(parent),
+        }),
+        DeleteType::Shallow => None,
+    }
+}
+
+// The tasks to delete each edge type from an object instance
+fn edge_tasks(edges: &Vec<&DelfEdge>, from_id: &String) -> Vec<Task> {
+    return edges
+        .iter()
+        .map(|e| Task::EdgeAll {
+            edge: e.name.clone(),
+            from_id: from_id.clone(),
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    const SCHEMA: &str = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_lead
+      to:
+        object_type: paper
+        field: lead_id
+      deletion: refcount
+    - name: contact_shepherd
+      to:
+        object_type: paper
+        field: shepherd_id
+      deletion: refcount
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+
+    fn seed(memory: &crate::storage::memory::MemoryConnection) {
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("contact", &[("id", "2")]);
+        memory.insert("paper", &[("id", "10"), ("lead_id", "1"), ("shepherd_id", "1")]);
+        memory.insert("paper", &[("id", "11"), ("lead_id", "1"), ("shepherd_id", "2")]);
+    }
+
+    #[test]
+    fn plan_follows_refcount_without_deleting() {
+        let (graph, memory) = crate::graph::test_graph(SCHEMA);
+        seed(&memory);
+
+        let plan = graph
+            .plan_delete_object(&String::from("contact"), &String::from("1"))
+            .unwrap();
+        let instances = plan.instances_by_storage();
+
+        assert_eq!(instances["s"]["contact"], vec!["1"]);
+        assert_eq!(instances["s"]["paper"], vec!["10"]);
+        assert_eq!(memory.column("contact", "id"), vec!["1", "2"]);
+        assert_eq!(memory.column("paper", "lead_id"), vec!["1", "1"]);
+    }
+
+    #[test]
+    fn plan_matches_deletion() {
+        let (graph, memory) = crate::graph::test_graph(SCHEMA);
+        seed(&memory);
+
+        let plan = graph
+            .plan_delete_object(&String::from("contact"), &String::from("1"))
+            .unwrap();
+        graph.delete_object(&String::from("contact"), &String::from("1")).unwrap();
+
+        assert_eq!(plan.object_count(), 2);
+        assert_eq!(memory.column("contact", "id"), vec!["2"]);
+        assert_eq!(memory.column("paper", "id"), vec!["11"]);
+    }
+
+    #[test]
+    fn deletes_only_the_edge_instance_given() {
+        let (graph, memory) = crate::graph::test_graph(SCHEMA);
+        seed(&memory);
+
+        let edge = String::from("contact_lead");
+        let (from_id, to_id) = (String::from("1"), String::from("10"));
+        assert!(graph.edge_exists(&edge, &from_id, &to_id).unwrap());
+        assert!(!graph.edge_exists(&edge, &String::from("2"), &to_id).unwrap());
+        // ids which aren't numbers are refused before they reach a query
+        assert!(!graph.edge_exists(&edge, &String::from("1 OR 1 = 1"), &to_id).unwrap());
+        assert!(graph.delete_edge(&edge, &from_id, &String::from("10'")).is_err());
+
+        let plan = graph.plan_delete_edge(&edge, &from_id, &to_id).unwrap();
+        assert_eq!(plan.edges[0].to_ids, vec!["10"]);
+
+        graph.delete_edge(&edge, &from_id, &to_id).unwrap();
+        assert_eq!(memory.column("paper", "lead_id"), vec!["0", "1"]);
+        assert_eq!(memory.column("paper", "id"), vec!["10", "11"]);
+        assert!(!graph.edge_exists(&edge, &from_id, &to_id).unwrap());
+    }
+
+    #[test]
+    fn refcount_counts_mapping_rows_from_other_instances() {
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: contact_id
+  edge_types:
+    - name: contact_conflict
+      to:
+        object_type: paper
+        field: contact_id
+        mapping_table: paper_conflict
+      deletion: refcount
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  id: paper_id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("contact", &[("contact_id", "1")]);
+        memory.insert("contact", &[("contact_id", "2")]);
+        memory.insert("paper", &[("paper_id", "10")]);
+        memory.insert("paper", &[("paper_id", "11")]);
+        for (paper_id, contact_id) in [("10", "1"), ("10", "2"), ("11", "1")].iter() {
+            memory.insert("paper_conflict", &[("paper_id", paper_id), ("contact_id", contact_id)]);
+        }
+
+        let contact = (String::from("contact"), String::from("1"));
+        let plan = graph.plan_delete_object(&contact.0, &contact.1).unwrap();
+        assert_eq!(plan.instances_by_storage()["s"]["paper"], vec!["11"]);
+
+        graph.delete_object(&contact.0, &contact.1).unwrap();
+        assert_eq!(memory.column("paper", "paper_id"), vec!["10"]);
+        assert_eq!(memory.column("paper_conflict", "contact_id"), vec!["2"]);
+    }
+
+    #[test]
+    fn deletes_each_instance_once_in_cycles() {
+        let schema = "
+object_type:
+  name: a
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: a_b
+      to:
+        object_type: b
+        field: a_id
+      deletion: deep
+      inverse: b_a
+---
+object_type:
+  name: b
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: b_a
+      to:
+        object_type: a
+        field: b_id
+      deletion: deep
+      inverse: a_b
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("a", &[("id", "1"), ("b_id", "2")]);
+        memory.insert("b", &[("id", "2"), ("a_id", "1")]);
+
+        let plan = graph
+            .plan_delete_object(&String::from("a"), &String::from("1"))
+            .unwrap();
+        graph.delete_object(&String::from("a"), &String::from("1")).unwrap();
+
+        assert_eq!(plan.object_count(), 2);
+        assert_eq!(memory.column("a", "id").len(), 0);
+        assert_eq!(memory.column("b", "id").len(), 0);
+    }
+
+    #[test]
+    fn child_first_removes_children_before_parents() {
+        use crate::storage::DelfStorageConnection;
+
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_paper
+      to:
+        object_type: paper
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: paper_review
+      to:
+        object_type: review
+        field: paper_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        // parallel cascades put off removals until the pool is done, so both are checked
+        for concurrency in [1, 2].iter() {
+            let url = format!("child_first_{}", crate::store::new_id());
+            let config = format!(
+                "storages:\n  - name: s\n    plugin: memory\n    url: {}\n    order: child_first\n    concurrency: {}\n",
+                url, concurrency
+            );
+            let graph = crate::read_yamls(schema, &config).unwrap();
+            let memory = crate::storage::memory::MemoryConnection::connect(&url);
+            memory.insert("contact", &[("id", "1")]);
+            memory.insert("paper", &[("id", "10"), ("contact_id", "1")]);
+            memory.insert("review", &[("id", "20"), ("paper_id", "10")]);
+            memory.insert("review", &[("id", "21"), ("paper_id", "10")]);
+
+            graph.delete_object(&String::from("contact"), &String::from("1")).unwrap();
+            let mut deleted: Vec<String> = memory
+                .deleted
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(table, _)| table.clone())
+                .collect();
+            deleted.dedup();
+            assert_eq!(deleted, vec!["review", "paper", "contact"]);
+        }
+    }
+}
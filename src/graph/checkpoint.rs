@@ -0,0 +1,267 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use yaml_rust::Yaml;
+
+use super::cascade::Cascade;
+use super::schema::{check_keys, required_str, SchemaProblem};
+use super::{DelfGraph, Deletion};
+use crate::store;
+
+const CHECKPOINTS_KEYS: [&str; 1] = ["dir"];
+
+/// Where cascade checkpoints are kept, from the `checkpoints` section of the config
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointConfig {
+    /// The directory holding a json file for each unfinished cascade
+    pub dir: String,
+}
+
+impl CheckpointConfig {
+    /// Read the `checkpoints` section of a config
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Result<CheckpointConfig, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(yaml, &CHECKPOINTS_KEYS, &mut problems);
+        let dir = required_str(yaml, "dir", &mut problems);
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(CheckpointConfig { dir: dir.unwrap() })
+    }
+}
+
+/// A piece of work in a cascade which hasn't finished yet
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Task {
+    /// Delete an object instance, and everything it cascades to
+    Object { object: String, id: String },
+    /// Delete all edges of a type from an object instance, and everything they cascade to
+    EdgeAll { edge: String, from_id: String },
+    /// Delete an edge instance, and everything it cascades to
+    EdgeOne {
+        edge: String,
+        from_id: String,
+        to_id: String,
+    },
+    /// Delete the object instance an edge from `from_id` points to if no other edge refers to it, and everything it cascades to
+    RefCount {
+        edge: String,
+        to_id: String,
+        #[serde(default)]
+        from_id: String,
+    },
+    /// Remove an object instance in a child-first storage, whose edges have already been handled, as if reached through the edge
+    Remove {
+        object: String,
+        id: String,
+        from_edge: Option<String>,
+    },
+    /// Purge a soft deleted object instance for good, along with everything it cascades to
+    Purge { object: String, id: String },
+}
+
+/// The frontier of an unfinished cascade.  Tasks are added before the object instance they start from is deleted, so the rest of the cascade can still be found if the process stops part way through.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The unique id of the cascade
+    pub id: String,
+    /// The id the deletion is logged under in the restoration log, if it is being logged
+    pub deletion_id: Option<String>,
+    /// The number of changes logged to the restoration log so far
+    pub sequence: usize,
+    /// The tasks not yet finished
+    pub pending: Vec<Task>,
+}
+
+impl Checkpoint {
+    // The file the checkpoint is kept in
+    fn path(&self, config: &CheckpointConfig) -> String {
+        return checkpoint_path(config, &self.id);
+    }
+
+    /// Load the checkpoint of the cascade with the id, if it is unfinished
+    pub(crate) fn load(config: &CheckpointConfig, id: &String) -> Result<Option<Checkpoint>, String> {
+        let path = checkpoint_path(config, id);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        return store::load(&path).map(Some);
+    }
+
+    /// Write the checkpoint to its file
+    pub(crate) fn save(&self, config: &CheckpointConfig) -> Result<(), String> {
+        std::fs::create_dir_all(&config.dir)
+            .map_err(|e| format!("couldn't create {}: {}", config.dir, e))?;
+        return store::save(&self.path(config), self);
+    }
+
+    /// Remove the checkpoint's file, once the cascade is finished
+    pub(crate) fn remove(&self, config: &CheckpointConfig) -> Result<(), String> {
+        let path = self.path(config);
+        if !Path::new(&path).exists() {
+            return Ok(());
+        }
+        return std::fs::remove_file(&path).map_err(|e| format!("couldn't remove {}: {}", path, e));
+    }
+
+    /// Load all the checkpoints of unfinished cascades
+    pub fn load_all(config: &CheckpointConfig) -> Result<Vec<Checkpoint>, String> {
+        if !Path::new(&config.dir).exists() {
+            return Ok(Vec::new());
+        }
+        let entries = std::fs::read_dir(&config.dir)
+            .map_err(|e| format!("couldn't read {}: {}", config.dir, e))?;
+
+        let mut checkpoints = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("couldn't read {}: {}", config.dir, e))?
+                .path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                checkpoints.push(store::load(&path.to_str().unwrap().to_string())?);
+            }
+        }
+        return Ok(checkpoints);
+    }
+}
+
+// The file the checkpoint of the cascade with the id is kept in
+fn checkpoint_path(config: &CheckpointConfig, id: &String) -> String {
+    return Path::new(&config.dir)
+        .join(format!("{}.json", id))
+        .to_str()
+        .unwrap()
+        .to_string();
+}
+
+impl DelfGraph {
+    /// Finish the cascades which were left unfinished when the process last stopped, returning the number resumed.  Does nothing if checkpoints aren't configured.
+    pub fn resume_cascades(&self) -> Result<usize, String> {
+        let config = match &self.checkpoints {
+            Some(config) => config,
+            None => return Ok(0),
+        };
+
+        let checkpoints = Checkpoint::load_all(config)?;
+        for checkpoint in checkpoints.iter() {
+            println!("Resuming cascade {}", checkpoint.id);
+            Cascade::resume(self, checkpoint.clone())?;
+        }
+        return Ok(checkpoints.len());
+    }
+
+    /// Finish the cascade with the id if it was left unfinished, returning what it deleted, or `None` if there is nothing to finish.
+    pub(crate) fn resume_cascade(&self, id: &String) -> Result<Option<Deletion>, String> {
+        let config = match &self.checkpoints {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        match Checkpoint::load(config, id)? {
+            Some(checkpoint) => Cascade::resume(self, checkpoint).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumes_cascade_after_parent_deleted() {
+        let dir = std::env::temp_dir().join(format!("delf_checkpoints_{}", store::new_id()));
+        let config = format!(
+            "storages: []\ncheckpoints:\n  dir: {}\n",
+            dir.to_str().unwrap()
+        );
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        memory.insert("review", &[("id", "20"), ("contact_id", "1")]);
+
+        // the process stopped after the contact was deleted, but before its reviews were
+        let checkpoint = Checkpoint {
+            id: String::from("stopped"),
+            deletion_id: None,
+            sequence: 0,
+            pending: vec![
+                Task::Object {
+                    object: String::from("contact"),
+                    id: String::from("1"),
+                },
+                Task::EdgeAll {
+                    edge: String::from("contact_review"),
+                    from_id: String::from("1"),
+                },
+            ],
+        };
+        checkpoint.save(graph.checkpoints.as_ref().unwrap()).unwrap();
+
+        assert_eq!(graph.resume_cascades(), Ok(1));
+        assert_eq!(memory.column("review", "id").len(), 0);
+        assert_eq!(graph.resume_cascades(), Ok(0));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn deletes_nothing_when_the_checkpoint_cant_be_written() {
+        // the checkpoint directory is a file, so it can't be created
+        let dir = std::env::temp_dir().join(format!("delf_checkpoints_{}", store::new_id()));
+        std::fs::write(&dir, "").unwrap();
+        let config = format!(
+            "storages: []\ncheckpoints:\n  dir: {}\n",
+            dir.to_str().unwrap()
+        );
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("review", &[("id", "20"), ("contact_id", "1")]);
+
+        assert!(graph.delete_object(&String::from("contact"), &String::from("1")).is_err());
+        assert_eq!(memory.column("contact", "id"), vec!["1"]);
+        assert_eq!(memory.column("review", "id"), vec!["20"]);
+
+        std::fs::remove_file(dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+
+use petgraph::{algo::tarjan_scc, visit::EdgeRef, Graph};
+
+use super::edge::{DelfEdge, DeleteType};
+use super::object::DelfObject;
+use super::DelfGraph;
+
+/// The kinds of cycles that can be found in a DelfGraph
+#[derive(Clone, Debug, PartialEq)]
+pub enum CycleType {
+    /// Objects connected in a loop of `deep` edges, so a cascade keeps recursing through the same objects
+    Deep,
+    /// Objects connected in a loop containing `refcount` edges, none of which can be deleted directly, so the objects can keep each other referenced and never be deleted
+    RefCount,
+}
+
+/// A strongly connected set of objects joined by `deep` and/or `refcount` edges
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelfCycle {
+    /// The kind of cycle found
+    pub cycle_type: CycleType,
+    /// The names of the objects in the cycle
+    pub objects: Vec<String>,
+    /// The names of the edges connecting the objects in the cycle
+    pub edges: Vec<String>,
+    /// The names of the `refcount` edges in the cycle
+    pub refcount_edges: Vec<String>,
+}
+
+impl DelfCycle {
+    /// A suggestion for how the schema could be changed to break the cycle
+    pub fn suggestion(&self) -> String {
+        match self.cycle_type {
+            CycleType::Deep => format!(
+                "change one of the edges ({}) to `shallow` so the cascade has a place to stop",
+                self.edges.join(", ")
+            ),
+            CycleType::RefCount => format!(
+                "change one of the `refcount` edges ({}) to `deep` or `shallow`, or make one of the objects ({}) deletable `directly` so the cycle can be broken",
+                self.refcount_edges.join(", "),
+                self.objects.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DelfCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.cycle_type {
+            CycleType::Deep => "deep",
+            CycleType::RefCount => "refcount",
+        };
+        write!(
+            f,
+            "{} cycle between objects [{}] via edges [{}]: {}",
+            kind,
+            self.objects.join(", "),
+            self.edges.join(", "),
+            self.suggestion()
+        )
+    }
+}
+
+impl DelfGraph {
+    /// Find the cycles in the graph which either recurse through `deep` edges, or where objects connected by `refcount` edges can keep each other from being deleted.
+    pub fn find_cycles(&self) -> Vec<DelfCycle> {
+        let mut cycles = self.find_cycles_of(CycleType::Deep, |e| e.deletion == DeleteType::Deep);
+
+        for cycle in self
+            .find_cycles_of(CycleType::RefCount, |e| e.deletion != DeleteType::Shallow)
+            .drain(..)
+        {
+            // a loop of only deep edges was already reported above, and deleting an object directly breaks the loop
+            if cycle.refcount_edges.len() > 0 && !self.any_directly_deletable(&cycle.objects) {
+                cycles.push(cycle);
+            }
+        }
+
+        return cycles;
+    }
+
+    // Whether any of the named objects can be deleted directly
+    fn any_directly_deletable(&self, objects: &Vec<String>) -> bool {
+        return objects
+            .iter()
+            .any(|name| self.get_object(name).deletable_by(None));
+    }
+
+    // Find the strongly connected components of the graph, keeping only the edges which pass the filter
+    fn find_cycles_of<F>(&self, cycle_type: CycleType, keep_edge: F) -> Vec<DelfCycle>
+    where
+        F: Fn(&DelfEdge) -> bool,
+    {
+        let filtered: Graph<&DelfObject, &DelfEdge> = self.graph.filter_map(
+            |_, n| Some(n),
+            |_, e| if keep_edge(e) { Some(e) } else { None },
+        );
+
+        let mut cycles = Vec::new();
+        for component in tarjan_scc(&filtered).iter() {
+            let mut edges = Vec::new();
+            let mut refcount_edges = Vec::new();
+            for node_id in component.iter() {
+                for e in filtered.edges(*node_id) {
+                    if component.contains(&e.target()) {
+                        edges.push(e.weight().name.clone());
+                        if e.weight().deletion == DeleteType::RefCount {
+                            refcount_edges.push(e.weight().name.clone());
+                        }
+                    }
+                }
+            }
+
+            // a single object is only a cycle if it has an edge to itself
+            if edges.len() == 0 {
+                continue;
+            }
+
+            let mut objects: Vec<String> = component
+                .iter()
+                .map(|node_id| filtered[*node_id].name.clone())
+                .collect();
+            objects.sort();
+            edges.sort();
+            refcount_edges.sort();
+
+            cycles.push(DelfCycle {
+                cycle_type: cycle_type.clone(),
+                objects,
+                edges,
+                refcount_edges,
+            });
+        }
+
+        return cycles;
+    }
+
+    // Ensure there are no deep or refcount cycles in the graph.
+    pub(crate) fn cycle_analysis(&self) -> Result<(), Vec<String>> {
+        let cycles = self.find_cycles();
+        if cycles.len() > 0 {
+            return Err(cycles.iter().map(|c| c.to_string()).collect());
+        } else {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "storages: []";
+
+    #[test]
+    fn finds_deep_cycle() {
+        let schema = "
+object_type:
+  name: a
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: a_to_b
+      to:
+        object_type: b
+        field: a_id
+      deletion: deep
+---
+object_type:
+  name: b
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: b_to_a
+      to:
+        object_type: a
+        field: b_id
+      deletion: deep
+";
+        let graph = crate::read_yamls(schema, CONFIG).unwrap();
+        let cycles = graph.find_cycles();
+
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].cycle_type, CycleType::Deep);
+        assert_eq!(cycles[0].objects, vec!["a", "b"]);
+        assert_eq!(cycles[0].edges, vec!["a_to_b", "b_to_a"]);
+    }
+
+    #[test]
+    fn finds_refcount_self_cycle() {
+        let schema = "
+object_type:
+  name: a
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: a_to_a
+      to:
+        object_type: a
+        field: parent_id
+      deletion: refcount
+    - name: a_to_a_shallow
+      to:
+        object_type: a
+        field: other_id
+      deletion: shallow
+";
+        let graph = crate::read_yamls(schema, CONFIG).unwrap();
+        let cycles = graph.find_cycles();
+
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].cycle_type, CycleType::RefCount);
+        assert_eq!(cycles[0].edges, vec!["a_to_a"]);
+
+        // an object which can be deleted directly breaks the loop
+        let graph = crate::read_yamls(&schema.replace("by_any", "directly"), CONFIG).unwrap();
+        assert!(graph.find_cycles().is_empty());
+    }
+
+    #[test]
+    fn example_has_no_cycles() {
+        let graph = crate::read_yamls(include_str!("../../examples/hotcrp/schema.yaml"), CONFIG).unwrap();
+        assert!(graph.find_cycles().is_empty());
+    }
+}
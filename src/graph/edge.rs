@@ -1,6 +1,13 @@
+use std::convert::TryFrom;
+use std::fmt;
+
 use yaml_rust::Yaml;
 
-use crate::graph::DelfGraph;
+use super::object::DelfObject;
+use super::predicate::Predicate;
+use super::schema::{check_keys, optional_str, required_str, SchemaProblem};
+use super::checkpoint::Task;
+use crate::graph::{DelfGraph, Deletion};
 
 /// The deletion types for a DelfEdge.  The type describes how the object the edge points to should be deleted by the DelfGraph.
 #[derive(Clone, Debug, PartialEq)]
@@ -9,17 +16,35 @@ pub enum DeleteType {
     Deep,
     /// Delete the edge, but not the object it refers to
     Shallow,
-    /// Delete the edge and delete the object if this edge is the last edge referring to it
+    /// Delete the edge and delete the object if this edge is the last edge referring to it.  Rows of a mapping table from other instances count as other edges.
     RefCount,
 }
 
 impl DeleteType {
-    fn from(input: &str) -> DeleteType {
+    /// Parse the deletion type of an edge
+    fn parse(input: &str) -> Result<DeleteType, SchemaProblem> {
         match input.to_lowercase().as_str() {
-            "deep" => DeleteType::Deep,
-            "shallow" => DeleteType::Shallow,
-            "refcount" => DeleteType::RefCount,
-            _ => panic!("No edge type"),
+            "deep" => Ok(DeleteType::Deep),
+            "shallow" => Ok(DeleteType::Shallow),
+            "refcount" => Ok(DeleteType::RefCount),
+            _ => Err(SchemaProblem::new(
+                "deletion",
+                format!(
+                    "unknown edge deletion type `{}`, expected one of: deep, shallow, refcount",
+                    input
+                ),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DeleteType {
+    /// Display the deletion type as it is written in the schema
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteType::Deep => write!(f, "deep"),
+            DeleteType::Shallow => write!(f, "shallow"),
+            DeleteType::RefCount => write!(f, "refcount"),
         }
     }
 }
@@ -30,6 +55,136 @@ pub struct ToType {
     pub object_type: String,
     pub field: String,
     pub mapping_table: Option<String>,
+    /// For a polymorphic edge, whose id column can refer to several object types, the column naming the type and the value for this object
+    pub discriminator: Option<Discriminator>,
+    /// For an edge through several join tables, the tables in order from the object the edge starts from
+    pub path: Option<Vec<Hop>>,
+}
+
+/// A join table of an edge's `path`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hop {
+    pub table: String,
+    /// The column holding the ids from the previous table, or the object the edge starts from for the first table
+    pub from: String,
+    /// The column holding the ids of the next table, or the object the edge points to for the last table
+    pub to: String,
+    /// The type of the ids in the `to` column.  Defaults to `number`, and is the id type of the object the edge points to for the last table.
+    pub id_type: String,
+}
+
+/// The type column of a polymorphic edge (like `commentable_type` next to `commentable_id`), and the value it holds for the object the edge points to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Discriminator {
+    pub column: String,
+    pub value: String,
+}
+
+/// Describes the column of the object the edge starts from which holds the id of the object it points to, for edges stored on their source (like a belongs-to relation)
+#[derive(Clone, Debug, PartialEq)]
+pub struct FromType {
+    /// The object the edge starts from, filled in when the graph is built
+    pub object_type: String,
+    pub field: String,
+    /// If the field holds a list of ids rather than a single one, how the list is stored
+    pub format: Option<ListFormat>,
+}
+
+/// How a field holding a list of ids stores them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListFormat {
+    /// A JSON array, like `[1, 2, 3]`
+    JsonArray,
+    /// Comma separated values, like `1,2,3`
+    Csv,
+}
+
+impl ListFormat {
+    /// Parse the format of a list of ids
+    fn parse(input: &str) -> Result<ListFormat, SchemaProblem> {
+        match input.to_lowercase().as_str() {
+            "json_array" => Ok(ListFormat::JsonArray),
+            "csv" => Ok(ListFormat::Csv),
+            _ => Err(SchemaProblem::new(
+                "format",
+                format!("unknown list format `{}`, expected one of: json_array, csv", input),
+            )),
+        }
+    }
+
+    /// The value of a field holding no ids
+    pub fn empty(&self) -> &'static str {
+        match self {
+            ListFormat::JsonArray => "[]",
+            ListFormat::Csv => "",
+        }
+    }
+
+    /// The ids in a field's value, as it is read from a row
+    pub fn ids(&self, value: &serde_json::Value) -> Vec<String> {
+        match value {
+            serde_json::Value::Array(elements) => elements.iter().map(element_id).collect(),
+            serde_json::Value::String(s) => self.split(s),
+            serde_json::Value::Null => Vec::new(),
+            v => vec![v.to_string()],
+        }
+    }
+
+    /// The ids in a field's value, written as a string
+    pub fn split(&self, value: &str) -> Vec<String> {
+        match self {
+            ListFormat::JsonArray => match serde_json::from_str::<Vec<serde_json::Value>>(value) {
+                Ok(elements) => elements.iter().map(element_id).collect(),
+                Err(_) => Vec::new(),
+            },
+            ListFormat::Csv => value
+                .split(',')
+                .map(|id| String::from(id.trim()))
+                .filter(|id| id.len() > 0)
+                .collect(),
+        }
+    }
+
+    /// A field's value, written as a string, with an id removed from the list
+    pub fn remove(&self, value: &str, id: &String) -> String {
+        match self {
+            ListFormat::JsonArray => match serde_json::from_str::<Vec<serde_json::Value>>(value) {
+                Ok(mut elements) => {
+                    elements.retain(|e| element_id(e) != *id);
+                    serde_json::to_string(&elements).unwrap()
+                }
+                Err(_) => String::from(value),
+            },
+            ListFormat::Csv => {
+                let ids: Vec<String> = self.split(value).into_iter().filter(|i| i != id).collect();
+                ids.join(",")
+            }
+        }
+    }
+}
+
+// The id an element of a JSON array holds, whether it is a string or a number
+fn element_id(element: &serde_json::Value) -> String {
+    match element {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// The value a field holding an id of the given type is set to when its edge is deleted
+pub fn cleared_id(id_type: &String) -> String {
+    match id_type.to_lowercase().as_str() {
+        "string" => String::new(),
+        _ => String::from("0"),
+    }
+}
+
+/// Check an id is of the given id type, so a `number` id must parse as a number
+pub fn check_id(id_type: &String, id: &String) -> Result<(), String> {
+    match id_type.to_lowercase().as_str() {
+        "number" if id.parse::<i64>().is_err() => Err(format!("{} isn't a number id", id)),
+        _ => Ok(()),
+    }
 }
 
 /// The DelfEdge contains the information about the edge as described in the schema
@@ -37,177 +192,508 @@ pub struct ToType {
 pub struct DelfEdge {
     /// The unique name identifying the edge, used by the API to enable deletion of an edge directly.
     pub name: String,
-    /// Describes the object the edge points to.
+    /// Describes the object the edge points to.  For an edge stored on its source, `to.field` is the id field of the object it points to.
     pub to: ToType,
+    /// If the edge is stored on the object it starts from, the column holding the id of the object it points to, from the `from` key.
+    pub from: Option<FromType>,
     /// How should the deletion of this edge affect the object it points to.
     pub deletion: DeleteType,
     /// If this edge is deleted (typically, shallowly), is there an inverse edge that also needs to be deleted.
     pub inverse: Option<String>,
+    /// Only the rows matching the predicate are edges, from the `where` key.  The predicate is on the table the edge is stored in: the mapping table if there is one, the table of the object it starts from if it has a `from` field, the first table of its `path` if it has one, otherwise the table of the object the edge points to.
+    pub predicate: Option<Predicate>,
 }
 
-impl From<&Yaml> for DelfEdge {
+// the keys which can be used in an edge definition
+const EDGE_KEYS: &[&str] = &["name", "from", "to", "deletion", "inverse", "where"];
+
+// the keys which can be used in the `from` definition of an edge
+const FROM_KEYS: &[&str] = &["field", "format"];
+
+// the keys which can be used in the `to` definition of an edge
+const TO_KEYS: &[&str] = &["object_type", "field", "mapping_table", "discriminator", "path"];
+
+// the keys which can be used in each table of the `path` of an edge
+const HOP_KEYS: &[&str] = &["table", "from", "to", "id_type"];
 
-    /// Create a DelfEdge from a yaml struct.  The keys `name`, `to` (which iteslf contains a yaml object with the fields `object_type`, `field`, and optionally `mapping_table`), and `deletion` are required.  An `inverse` key may also be specified.
-    fn from(obj: &Yaml) -> DelfEdge {
-        DelfEdge {
-            name: String::from(obj["name"].as_str().unwrap()),
-            to: ToType::from(&obj["to"]),
-            deletion: DeleteType::from(obj["deletion"].as_str().unwrap()),
-            inverse: match obj["inverse"].as_str() {
-                Some(edge_name) => Some(String::from(edge_name)),
-                None => None,
-            }, // gets updated later if needed
+// the keys which can be used in the `discriminator` definition of an edge
+const DISCRIMINATOR_KEYS: &[&str] = &["column", "value"];
+
+impl TryFrom<&Yaml> for DelfEdge {
+    type Error = Vec<SchemaProblem>;
+
+    /// Create a DelfEdge from a yaml struct.  The keys `name`, `to` (which iteslf contains a yaml object with the fields `object_type`, `field`, and optionally `mapping_table`), and `deletion` are required.  An edge stored on the object it starts from instead gives `from` (a yaml object with the `field` holding the id of the object pointed to, and optionally the `format` of a field holding a list of ids: `json_array` or `csv`), and no `to.field` or `to.mapping_table`.  An `inverse` key, and a `where` predicate limiting which rows are edges, may also be specified.  All problems found in the yaml are returned together.
+    fn try_from(obj: &Yaml) -> Result<DelfEdge, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(obj, EDGE_KEYS, &mut problems);
+
+        let name = required_str(obj, "name", &mut problems);
+        let from = match &obj["from"] {
+            Yaml::BadValue => None,
+            from_yaml => {
+                let mut from_problems = Vec::new();
+                check_keys(from_yaml, FROM_KEYS, &mut from_problems);
+                let field = required_str(from_yaml, "field", &mut from_problems);
+                let format = match optional_str(from_yaml, "format", &mut from_problems) {
+                    Some(f) => match ListFormat::parse(&f) {
+                        Ok(format) => Some(format),
+                        Err(e) => {
+                            from_problems.push(e);
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                problems.extend(from_problems.into_iter().map(|p| p.within("from")));
+                field.map(|field| FromType {
+                    object_type: String::new(), // filled in when the graph is built
+                    field,
+                    format,
+                })
+            }
+        };
+        let to = match &obj["to"] {
+            Yaml::BadValue => {
+                problems.push(SchemaProblem::new(
+                    "to",
+                    String::from("missing required key `to`"),
+                ));
+                None
+            }
+            to_yaml => match ToType::parse(to_yaml, obj["from"].is_badvalue()) {
+                Ok(to) => Some(to),
+                Err(e) => {
+                    problems.extend(e.into_iter().map(|p| p.within("to")));
+                    None
+                }
+            },
+        };
+        let deletion = match required_str(obj, "deletion", &mut problems) {
+            Some(d) => match DeleteType::parse(&d) {
+                Ok(deletion) => Some(deletion),
+                Err(e) => {
+                    problems.push(e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let inverse = optional_str(obj, "inverse", &mut problems); // gets updated later if needed
+        let predicate = match optional_str(obj, "where", &mut problems) {
+            Some(p) => match Predicate::parse(&p) {
+                Ok(predicate) => Some(predicate),
+                Err(e) => {
+                    problems.push(SchemaProblem::new("where", e));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        if problems.len() > 0 {
+            return Err(problems);
         }
+
+        Ok(DelfEdge {
+            name: name.unwrap(),
+            to: to.unwrap(),
+            from,
+            deletion: deletion.unwrap(),
+            inverse,
+            predicate,
+        })
     }
 }
 
-impl From<&Yaml> for ToType {
-    /// Create a ToType from a yaml struct. The expected keys are `object_type`, `field`, and optionally `mapping_table`.
-    fn from(obj: &Yaml) -> ToType {
-        ToType {
-            object_type: String::from(obj["object_type"].as_str().unwrap()),
-            field: String::from(obj["field"].as_str().unwrap()),
-            mapping_table: match obj["mapping_table"].as_str() {
-                Some(table) => Some(String::from(table)),
-                None => None,
-            },
-        }
+impl TryFrom<&Yaml> for ToType {
+    type Error = Vec<SchemaProblem>;
+
+    /// Create a ToType from a yaml struct. The expected keys are `object_type`, `field`, and optionally `mapping_table` (or instead of both, a `path` of join tables, each with its `table`, `from` and `to` columns, and optionally the `id_type` of the `to` column) and a `discriminator` (a yaml object with the type `column` and the `value` it holds for this object).  All problems found in the yaml are returned together.
+    fn try_from(obj: &Yaml) -> Result<ToType, Vec<SchemaProblem>> {
+        return ToType::parse(obj, true);
     }
 }
 
-impl DelfEdge {
-    /// Delete a specific edge between two object instances
-    pub fn delete_one(&self, from_id: &String, to_id: &String, graph: &DelfGraph) {
-        let to_obj = graph.get_object(&self.to.object_type);
-        let s = &*(graph.storages.get(&to_obj.storage).unwrap());
+impl ToType {
+    // Parse a ToType, which only has the `object_type` key if the edge is stored on the object it starts from
+    fn parse(obj: &Yaml, stored_on_target: bool) -> Result<ToType, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(obj, TO_KEYS, &mut problems);
 
-        match self.deletion {
-            DeleteType::Deep => {
-                graph._delete_object(&to_obj.name, to_id, Some(self));
+        let object_type = required_str(obj, "object_type", &mut problems);
+        let field;
+        let mapping_table;
+        let mut path = None;
+        if !stored_on_target {
+            for key in ["field", "mapping_table", "path"].iter() {
+                if !obj[*key].is_badvalue() {
+                    problems.push(SchemaProblem::new(
+                        key,
+                        format!("`{}` can't be given for an edge with `from`, which is stored on the object it starts from", key),
+                    ));
+                }
             }
-            DeleteType::RefCount => {
-                let inbound_edges = graph.get_inbound_edges(to_obj);
-                let mut last_ref = true;
-                for inbound_edge in inbound_edges.iter() {
-                    if *inbound_edge != self {
-                        if s.has_edge(to_obj, to_id, inbound_edge) {
-                            last_ref = false;
-                            break;
-                        }
-                    }
+            field = Some(String::new()); // the id field of the object, filled in when the graph is built
+            mapping_table = None;
+        } else if !obj["path"].is_badvalue() {
+            for key in ["field", "mapping_table"].iter() {
+                if !obj[*key].is_badvalue() {
+                    problems.push(SchemaProblem::new(
+                        key,
+                        format!("`{}` can't be given for an edge with a `path`, which gives the columns of each table", key),
+                    ));
                 }
-
-                if last_ref {
-                    graph._delete_object(&to_obj.name, to_id, Some(self));
+            }
+            path = parse_path(&obj["path"], &mut problems);
+            // the first table holds the ids of the object the edge starts from
+            field = Some(path.as_ref().map_or(String::new(), |hops| hops[0].from.clone()));
+            mapping_table = None;
+        } else {
+            field = required_str(obj, "field", &mut problems);
+            mapping_table = optional_str(obj, "mapping_table", &mut problems);
+        }
+        let discriminator = match &obj["discriminator"] {
+            Yaml::BadValue => None,
+            d => {
+                let mut d_problems = Vec::new();
+                check_keys(d, DISCRIMINATOR_KEYS, &mut d_problems);
+                let column = required_str(d, "column", &mut d_problems);
+                let value = required_str(d, "value", &mut d_problems);
+                problems.extend(d_problems.into_iter().map(|p| p.within("discriminator")));
+                match (column, value) {
+                    (Some(column), Some(value)) => Some(Discriminator { column, value }),
+                    _ => None,
                 }
             }
-            DeleteType::Shallow => (),
+        };
+
+        if problems.len() > 0 {
+            return Err(problems);
         }
 
-        let deleted = s.delete_edge(to_obj, from_id, None, self);
+        Ok(ToType {
+            object_type: object_type.unwrap(),
+            field: field.unwrap(),
+            mapping_table,
+            discriminator,
+            path,
+        })
+    }
+}
 
-        if deleted {
-            println!("Edge deleted: {:#?}", self.name);
-            match &self.inverse {
-                Some(inverse) => {
-                    graph.delete_edge(&inverse, to_id, from_id);
-                }
-                None => (),
-            }
+// Parse the `path` of an edge, a list of the join tables it goes through
+fn parse_path(yaml: &Yaml, problems: &mut Vec<SchemaProblem>) -> Option<Vec<Hop>> {
+    let hop_yamls = match yaml.as_vec() {
+        Some(hops) if hops.len() > 0 => hops,
+        _ => {
+            problems.push(SchemaProblem::new(
+                "path",
+                String::from("`path` must be a list of the join tables the edge goes through"),
+            ));
+            return None;
+        }
+    };
+
+    let mut hops = Vec::new();
+    let mut hop_problems = Vec::new();
+    for (i, hop) in hop_yamls.iter().enumerate() {
+        let mut p = Vec::new();
+        check_keys(hop, HOP_KEYS, &mut p);
+        let table = required_str(hop, "table", &mut p);
+        let from = required_str(hop, "from", &mut p);
+        let to = required_str(hop, "to", &mut p);
+        let id_type = optional_str(hop, "id_type", &mut p);
+        hop_problems.extend(p.into_iter().map(|p| p.within(&format!("path[{}]", i))));
+        if let (Some(table), Some(from), Some(to)) = (table, from, to) {
+            hops.push(Hop {
+                table,
+                from,
+                to,
+                id_type: id_type.unwrap_or(String::from("number")),
+            });
         }
     }
 
-    /// Delete all edges of a given type from the instance of the object
-    pub fn delete_all(&self, from_id: &String, from_id_type: &String, graph: &DelfGraph) {
-        let to_obj = graph.get_object(&self.to.object_type);
-        let s = &*(graph.storages.get(&to_obj.storage).unwrap());
+    if hop_problems.len() > 0 {
+        problems.extend(hop_problems);
+        return None;
+    }
+    return Some(hops);
+}
 
-        let table = match &self.to.mapping_table {
-            Some(tbl) => tbl,
-            None => &to_obj.name,
-        };
+impl DelfEdge {
+    /// The table the edge is stored in: the mapping table if there is one, the table of the object it starts from if it has a `from` field, the first table of its `path` if it has one, otherwise the table of the object the edge points to
+    pub fn table(&self) -> &String {
+        match (&self.to.mapping_table, &self.from, &self.to.path) {
+            (Some(table), _, _) => table,
+            (None, Some(from), _) => &from.object_type,
+            (None, None, Some(hops)) => &hops[0].table,
+            (None, None, None) => &self.to.object_type,
+        }
+    }
 
-        match self.deletion {
-            DeleteType::Deep => {
-                // collect object ids to delete
-                let to_ids = s.get_object_ids(
-                    from_id,
-                    from_id_type,
-                    &self.to.field,
-                    table,
-                    &to_obj.id_field,
-                    &to_obj.id_type,
-                );
-                for to_id in to_ids.iter() {
-                    graph._delete_object(&to_obj.name, to_id, Some(self));
-                }
-            }
-            DeleteType::RefCount => {
-                let to_ids = s.get_object_ids(
-                    from_id,
-                    from_id_type,
-                    &self.to.field,
-                    table,
-                    &to_obj.id_field,
-                    &to_obj.id_type,
-                );
-                let inbound_edges = graph.get_inbound_edges(to_obj);
-                for to_id in to_ids.iter() {
-                    let mut last_ref = true;
-                    for inbound_edge in inbound_edges.iter() {
-                        if *inbound_edge != self {
-                            if s.has_edge(to_obj, to_id, inbound_edge) {
-                                last_ref = false;
-                                break;
-                            }
-                        }
-                    }
+    /// The predicate rows of the table the edge is stored in must match to be edges: the `where` predicate, and the discriminator's value for a polymorphic edge
+    pub fn row_predicate(&self) -> Option<Predicate> {
+        match &self.to.discriminator {
+            Some(d) => Some(Predicate::and(
+                self.predicate.as_ref(),
+                Predicate::equals(&d.column, &d.value),
+            )),
+            None => self.predicate.clone(),
+        }
+    }
 
-                    if last_ref {
-                        graph._delete_object(&to_obj.name, to_id, Some(self));
-                    }
-                }
-            }
-            _ => (),
-        }
-
-        match &self.inverse {
-            Some(inverse) => {
-                let table: &String;
-                match &self.to.mapping_table {
-                    Some(mapping_table) => {
-                        table = &mapping_table;
-                    }
-                    None => {
-                        table = &to_obj.name;
-                    }
-                }
-                // collect object ids to delete
-                let to_ids = s.get_object_ids(
-                    from_id,
-                    from_id_type,
-                    &self.to.field,
-                    table,
-                    &to_obj.id_field,
-                    &to_obj.id_type,
-                );
-                for to_id in to_ids.iter() {
-                    graph.delete_edge(&inverse, to_id, from_id);
-                }
-            }
-            None => (),
+    /// The predicate on the rows of the table the edge is stored in which point to the instance of `to` with the id
+    pub fn points_to(&self, to: &DelfObject, id: &String) -> Predicate {
+        match &self.from {
+            Some(FromType {
+                field,
+                format: Some(format),
+                ..
+            }) => Predicate::contains(field, *format, &to.id_type, id),
+            Some(from) => Predicate::equals(&from.field, id),
+            None => Predicate::equals(&to.id_field, id),
         }
+    }
 
-        if s.delete_edge(to_obj, from_id, None, self) {
-            println!("Edges Deleted: {:#?}", self.name);
+    /// The column of the table the edge is stored in which holds the id of an object instance: the `from` field if there is one, otherwise the `to` field holding the id of the object it starts from
+    pub fn field(&self) -> &String {
+        match &self.from {
+            Some(from) => &from.field,
+            None => &self.to.field,
         }
     }
 
+    /// Delete a specific edge between two object instances, along with everything it cascades to
+    pub fn delete_one(&self, from_id: &String, to_id: &String, graph: &DelfGraph) -> Result<Deletion, String> {
+        return graph.delete_edge(&self.name, from_id, to_id);
+    }
+
+    /// Delete all edges of a given type from the instance of the object, along with everything they cascade to
+    pub fn delete_all(&self, from_id: &String, graph: &DelfGraph) -> Result<Deletion, String> {
+        let task = Task::EdgeAll {
+            edge: self.name.clone(),
+            from_id: from_id.clone(),
+        };
+        return graph.delete_task(task, None);
+    }
+
     /// Validate the edge exists in the storage as described in the schema
     pub fn validate(&self, graph: &DelfGraph) -> Result<(), String> {
         let to_obj = graph.get_object(&self.to.object_type);
-        let s = &*(graph.storages.get(&to_obj.storage).unwrap());
-        let res = s.validate_edge(self);
-        return res;
+        match graph.storages.get(&to_obj.storage) {
+            Some(s) => s.validate_edge(self),
+            None => Err(format!(
+                "Edge {} points to an object using storage {}, which isn't in the config",
+                self.name, to_obj.storage
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn follows_edges_stored_on_their_source() {
+        let schema = "
+object_type:
+  name: paper
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: paper_lead
+      from:
+        field: lead_id
+      to:
+        object_type: contact
+      deletion: refcount
+---
+object_type:
+  name: contact
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("contact", &[("id", "2")]);
+        memory.insert("paper", &[("id", "10"), ("lead_id", "1")]);
+        memory.insert("paper", &[("id", "11"), ("lead_id", "1")]);
+        memory.insert("paper", &[("id", "12"), ("lead_id", "2")]);
+
+        let paper = String::from("paper");
+        graph.delete_object(&paper, &String::from("10")).unwrap();
+        assert_eq!(memory.column("contact", "id"), vec!["1", "2"]);
+
+        let edge = String::from("paper_lead");
+        graph.delete_edge(&edge, &String::from("12"), &String::from("2")).unwrap();
+        assert_eq!(memory.column("paper", "lead_id"), vec!["1", "0"]);
+        assert_eq!(memory.column("contact", "id"), vec!["1"]);
+
+        let plan = graph.plan_delete_object(&paper, &String::from("11")).unwrap();
+        assert_eq!(plan.instances_by_storage()["s"]["contact"], vec!["1"]);
+    }
+
+    #[test]
+    fn follows_edges_stored_as_lists() {
+        let schema = "
+object_type:
+  name: paper
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: paper_authors
+      from:
+        field: author_ids
+        format: json_array
+      to:
+        object_type: contact
+      deletion: refcount
+    - name: paper_tags
+      from:
+        field: tag_ids
+        format: csv
+      to:
+        object_type: tag
+      deletion: shallow
+---
+object_type:
+  name: contact
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+---
+object_type:
+  name: tag
+  storage: s
+  deletion: directly
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        for id in ["1", "2", "3"].iter() {
+            memory.insert("contact", &[("id", id)]);
+        }
+        memory.insert("paper", &[("id", "10"), ("author_ids", "[1, 2]"), ("tag_ids", "5,6, 7")]);
+        memory.insert("paper", &[("id", "11"), ("author_ids", "[2, \"3\"]"), ("tag_ids", "")]);
+
+        graph.delete_edge(&String::from("paper_tags"), &String::from("10"), &String::from("6")).unwrap();
+        assert_eq!(memory.column("paper", "tag_ids"), vec!["5,7", ""]);
+        assert!(!graph.edge_exists(&String::from("paper_authors"), &String::from("10"), &String::from("3")).unwrap());
+
+        graph.delete_object(&String::from("paper"), &String::from("11")).unwrap();
+        assert_eq!(memory.column("contact", "id"), vec!["1", "2"]);
+        assert_eq!(memory.column("paper", "author_ids"), vec!["[1, 2]"]);
+
+        graph.delete_edge(&String::from("paper_authors"), &String::from("10"), &String::from("1")).unwrap();
+        assert_eq!(memory.column("paper", "author_ids"), vec!["[2]"]);
+        assert_eq!(memory.column("contact", "id"), vec!["2"]);
+    }
+
+    #[test]
+    fn follows_paths_through_join_tables() {
+        let schema = "
+object_type:
+  name: user
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: user_projects
+      to:
+        object_type: project
+        path:
+          - table: team_membership
+            from: user_id
+            to: team_id
+          - table: team_project
+            from: team_id
+            to: project_id
+      deletion: refcount
+---
+object_type:
+  name: project
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("user", &[("id", "1")]);
+        memory.insert("user", &[("id", "2")]);
+        memory.insert("project", &[("id", "100")]);
+        memory.insert("project", &[("id", "101")]);
+        for (user_id, team_id) in [("1", "5"), ("2", "5"), ("1", "6")].iter() {
+            memory.insert("team_membership", &[("user_id", user_id), ("team_id", team_id)]);
+        }
+        for (team_id, project_id) in [("5", "100"), ("6", "101"), ("6", "100")].iter() {
+            memory.insert("team_project", &[("team_id", team_id), ("project_id", project_id)]);
+        }
+
+        let edge = String::from("user_projects");
+        let plan = graph.plan_delete_object(&String::from("user"), &String::from("1")).unwrap();
+        assert_eq!(plan.instances_by_storage()["s"]["project"], vec!["101"]);
+
+        graph.delete_edge(&edge, &String::from("1"), &String::from("101")).unwrap();
+        assert_eq!(memory.column("team_membership", "team_id"), vec!["5", "5"]);
+        assert_eq!(memory.column("project", "id"), vec!["100"]);
+
+        graph.delete_object(&String::from("user"), &String::from("2")).unwrap();
+        assert_eq!(memory.column("team_membership", "user_id"), vec!["1"]);
+        assert_eq!(memory.column("project", "id"), vec!["100"]);
+    }
+
+    #[test]
+    fn polymorphic_edges_only_follow_their_type() {
+        let schema = "
+object_type:
+  name: paper
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: paper_comment
+      to:
+        object_type: comment
+        field: commentable_id
+        discriminator:
+          column: commentable_type
+          value: Paper
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: review_comment
+      to:
+        object_type: comment
+        field: commentable_id
+        discriminator:
+          column: commentable_type
+          value: Review
+      deletion: deep
+---
+object_type:
+  name: comment
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("paper", &[("id", "1")]);
+        memory.insert("review", &[("id", "1")]);
+        memory.insert("comment", &[("id", "10"), ("commentable_type", "Paper"), ("commentable_id", "1")]);
+        memory.insert("comment", &[("id", "11"), ("commentable_type", "Review"), ("commentable_id", "1")]);
+
+        graph.delete_object(&String::from("paper"), &String::from("1")).unwrap();
+        assert_eq!(memory.column("comment", "id"), vec!["11"]);
     }
 }
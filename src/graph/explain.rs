@@ -0,0 +1,459 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use ansi_term::Colour::{Cyan, Green, Red};
+use petgraph::{visit::EdgeRef, Incoming, Outgoing};
+
+use super::{edge, object, DelfGraph};
+
+/// The most deletion paths an explanation lists.  Objects reached through many branches can have a great many paths, so the rest are only counted.
+pub const MAX_PATHS: usize = 100;
+
+/// A single hop along a path through the graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathStep {
+    /// The name of the edge followed
+    pub edge: String,
+    /// The deletion type of the edge followed
+    pub deletion: edge::DeleteType,
+    /// The name of the object the edge points to
+    pub to: String,
+}
+
+impl fmt::Display for PathStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, " -[{}: {}]-> {}", self.edge, self.deletion, self.to)
+    }
+}
+
+/// A path which deletes an object, starting from an object that can be deleted directly (`directly`, `directly_only`, or `short_ttl`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeletionPath {
+    /// The name of the object the path starts from
+    pub root: String,
+    /// The deletion type of the object the path starts from
+    pub root_deletion: object::DeleteType,
+    /// The edges followed from the root to the object being deleted
+    pub steps: Vec<PathStep>,
+}
+
+impl fmt::Display for DeletionPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.root, self.root_deletion)?;
+        for step in self.steps.iter() {
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// A path towards an object which is cut off by an edge that can't delete the object it points to
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockedPath {
+    /// The name of the object the blocking edge starts from
+    pub from: String,
+    /// The shortest path deleting `from`, if there is one
+    pub from_path: Option<DeletionPath>,
+    /// The edge which stops the deletion
+    pub blocked: PathStep,
+    /// The edges that would have been followed from the blocked object to the object being explained
+    pub rest: Vec<PathStep>,
+    /// Why the edge does not delete the object it points to
+    pub reason: String,
+}
+
+impl fmt::Display for BlockedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.from_path {
+            Some(path) => write!(f, "{}", path)?,
+            None => write!(f, "{} (not deletable)", self.from)?,
+        }
+        write!(
+            f,
+            " -[{}: {}]-X-> {}",
+            self.blocked.edge, self.blocked.deletion, self.blocked.to
+        )?;
+        for step in self.rest.iter() {
+            write!(f, "{}", step)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// An explanation of how an object gets deleted, or why it can't be.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// The name of the object being explained
+    pub object: String,
+    /// The deletion type of the object being explained
+    pub deletion: object::DeleteType,
+    /// Every path from a directly deletable object which deletes this object, shortest first, up to `MAX_PATHS`
+    pub paths: Vec<DeletionPath>,
+    /// The number of deletion paths left out of `paths`
+    pub more_paths: usize,
+    /// If there are no deletion paths, the nearest edges stopping this object from being deleted
+    pub blocked: Vec<BlockedPath>,
+}
+
+impl Explanation {
+    /// Whether the object can be deleted at all
+    pub fn is_deletable(&self) -> bool {
+        return self.paths.len() > 0;
+    }
+
+    /// Pretty print the explanation.
+    pub fn print(&self) {
+        println!(
+            "\u{1f50d} {}",
+            Cyan.bold().paint(format!(
+                "Explaining deletion of {} ({})",
+                self.object, self.deletion
+            ))
+        );
+
+        if self.is_deletable() {
+            println!(
+                "\u{2705} {}",
+                Green.paint(format!(
+                    "{} deletion path(s) found",
+                    self.paths.len() + self.more_paths
+                ))
+            );
+            for path in self.paths.iter() {
+                println!("  {}", path);
+            }
+            if self.more_paths > 0 {
+                println!("  {} more paths", self.more_paths);
+            }
+        } else {
+            println!(
+                "\u{274c} {}",
+                Red.paint(format!("{} is not deletable", self.object))
+            );
+            match self.deletion {
+                object::DeleteType::NotDeleted => {
+                    println!("  {} is `not_deleted`, so it is never deleted", self.object)
+                }
+                _ => {
+                    if self.blocked.len() == 0 {
+                        println!(
+                            "  no edges point to {} and `{}` objects can not be deleted directly",
+                            self.object, self.deletion
+                        );
+                    }
+                    for path in self.blocked.iter() {
+                        println!("  {}", path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl DelfGraph {
+    /// Explain how the object with the given name is deleted by listing every path from a directly deletable object (`directly`, `directly_only`, or `short_ttl`) to it, the shortest `MAX_PATHS` first.  If the object can't be deleted, the nearest blocked paths are listed instead.
+    pub fn explain(&self, object_name: &String) -> Result<Explanation, String> {
+        if !self.nodes.contains_key(object_name) {
+            return Err(format!("No object with name {:#?}", object_name));
+        }
+
+        let obj = self.get_object(object_name);
+        let mut paths = self.deletion_paths(object_name);
+        let more_paths = paths.len().saturating_sub(MAX_PATHS);
+        paths.truncate(MAX_PATHS);
+        let blocked = if paths.len() == 0 {
+            self.blocked_paths(object_name)
+        } else {
+            Vec::new()
+        };
+
+        return Ok(Explanation {
+            object: obj.name.clone(),
+            deletion: obj.deletion.clone(),
+            paths,
+            more_paths,
+            blocked,
+        });
+    }
+
+    // Find every path from a root object which ends up deleting the named object, shortest first
+    fn deletion_paths(&self, object_name: &String) -> Vec<DeletionPath> {
+        let mut paths = Vec::new();
+        for (_, node_id) in self.nodes.iter() {
+            let obj = self.graph.node_weight(*node_id).unwrap();
+            match obj.deletion {
+                object::DeleteType::ShortTTL
+                | object::DeleteType::Directly
+                | object::DeleteType::DirectlyOnly => {
+                    let mut on_path = HashSet::new();
+                    let mut path = DeletionPath {
+                        root: obj.name.clone(),
+                        root_deletion: obj.deletion.clone(),
+                        steps: Vec::new(),
+                    };
+                    self.walk_paths(&obj.name, object_name, &mut path, &mut on_path, &mut paths);
+                }
+                _ => (),
+            }
+        }
+
+        paths.sort_by(|a, b| {
+            a.steps
+                .len()
+                .cmp(&b.steps.len())
+                .then_with(|| a.to_string().cmp(&b.to_string()))
+        });
+        return paths;
+    }
+
+    // Depth first search for simple paths from the current object to the target along edges which delete the object they point to, never revisiting an object already on the path
+    fn walk_paths(
+        &self,
+        current: &String,
+        target: &String,
+        path: &mut DeletionPath,
+        on_path: &mut HashSet<String>,
+        paths: &mut Vec<DeletionPath>,
+    ) {
+        if current == target {
+            paths.push(path.clone());
+            return;
+        }
+
+        on_path.insert(current.clone());
+        for e in self.graph.edges_directed(self.nodes[current], Outgoing) {
+            let ew = e.weight();
+            let to_obj = self.graph.node_weight(e.target()).unwrap();
+            if on_path.contains(&to_obj.name) || edge_blocked_reason(ew, to_obj).is_some() {
+                continue;
+            }
+
+            path.steps.push(PathStep {
+                edge: ew.name.clone(),
+                deletion: ew.deletion.clone(),
+                to: to_obj.name.clone(),
+            });
+            self.walk_paths(&to_obj.name, target, path, on_path, paths);
+            path.steps.pop();
+        }
+        on_path.remove(current);
+    }
+
+    // Walk backwards from the named object to find the nearest edges which stop it from being deleted
+    fn blocked_paths(&self, object_name: &String) -> Vec<BlockedPath> {
+        let mut blocked = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+
+        visited.insert(object_name.clone());
+        queue.push_back((object_name.clone(), Vec::<PathStep>::new()));
+
+        while let Some((current, rest)) = queue.pop_front() {
+            let to_obj = self.get_object(&current);
+            for e in self.graph.edges_directed(self.nodes[&current], Incoming) {
+                let ew = e.weight();
+                let from_obj = self.graph.node_weight(e.source()).unwrap();
+                let step = PathStep {
+                    edge: ew.name.clone(),
+                    deletion: ew.deletion.clone(),
+                    to: current.clone(),
+                };
+
+                match edge_blocked_reason(ew, to_obj) {
+                    Some(reason) => blocked.push(BlockedPath {
+                        from: from_obj.name.clone(),
+                        from_path: self.deletion_paths(&from_obj.name).into_iter().next(),
+                        blocked: step,
+                        rest: rest.clone(),
+                        reason,
+                    }),
+                    None => {
+                        // the edge would delete the object, so the problem is further up the graph
+                        if !visited.contains(&from_obj.name) {
+                            visited.insert(from_obj.name.clone());
+                            let mut from_rest = vec![step];
+                            from_rest.extend(rest.iter().cloned());
+                            queue.push_back((from_obj.name.clone(), from_rest));
+                        }
+                    }
+                }
+            }
+        }
+
+        return blocked;
+    }
+}
+
+// If following the edge would not delete the object it points to, the reason why
+fn edge_blocked_reason(e: &edge::DelfEdge, to_obj: &object::DelfObject) -> Option<String> {
+    if e.deletion == edge::DeleteType::Shallow {
+        return Some(String::from(
+            "`shallow` edges do not delete the object they point to",
+        ));
+    }
+    if to_obj.deletable_by(Some(e)) {
+        return None;
+    }
+
+    match &to_obj.deletion {
+        object::DeleteType::NotDeleted => Some(format!(
+            "{} is `not_deleted`, so it is never deleted",
+            to_obj.name
+        )),
+        object::DeleteType::ByXOnly(x) => {
+            let mut names: Vec<&String> = x.iter().collect();
+            names.sort();
+            Some(format!(
+                "{} is `by_x_only` and {} is not one of its edges ({})",
+                to_obj.name,
+                e.name,
+                names
+                    .iter()
+                    .map(|n| n.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))
+        }
+        deletion => Some(format!(
+            "{} is `{}`, so it can not be deleted through an edge",
+            to_obj.name, deletion
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SCHEMA: &str = "
+object_type:
+  name: user
+  storage: s
+  deletion: directly_only
+  id: id
+  edge_types:
+    - name: user_photo
+      to:
+        object_type: photo
+        field: user_id
+      deletion: deep
+    - name: user_friend
+      to:
+        object_type: friendship
+        field: user_id
+      deletion: shallow
+    - name: user_option
+      to:
+        object_type: option
+        field: user_id
+      deletion: deep
+---
+object_type:
+  name: photo
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+---
+object_type:
+  name: friendship
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+---
+object_type:
+  name: option
+  storage: s
+  deletion: by_x_only
+  x:
+    - somewhere_else
+  id: id
+  edge_types: []
+";
+
+    #[test]
+    fn explains_deletion_paths() {
+        let graph = crate::read_yamls(SCHEMA, "storages: []").unwrap();
+        let explanation = graph.explain(&String::from("photo")).unwrap();
+
+        assert!(explanation.is_deletable());
+        assert_eq!(explanation.paths.len(), 1);
+        assert_eq!(
+            explanation.paths[0].to_string(),
+            "user (directly_only) -[user_photo: deep]-> photo"
+        );
+    }
+
+    #[test]
+    fn lists_every_path_shortest_first() {
+        let schema = "
+object_type:
+  name: a
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: a_b
+      to:
+        object_type: b
+        field: a_id
+      deletion: deep
+    - name: a_c
+      to:
+        object_type: c
+        field: a_id
+      deletion: deep
+---
+object_type:
+  name: b
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: b_c
+      to:
+        object_type: c
+        field: b_id
+      deletion: deep
+---
+object_type:
+  name: c
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let graph = crate::read_yamls(schema, "storages: []").unwrap();
+        let explanation = graph.explain(&String::from("c")).unwrap();
+
+        assert_eq!(explanation.paths.len(), 2);
+        assert_eq!(explanation.more_paths, 0);
+        assert_eq!(
+            explanation.paths[0].to_string(),
+            "a (directly) -[a_c: deep]-> c"
+        );
+        assert_eq!(
+            explanation.paths[1].to_string(),
+            "a (directly) -[a_b: deep]-> b -[b_c: deep]-> c"
+        );
+    }
+
+    #[test]
+    fn explains_blocked_paths() {
+        let graph = crate::read_yamls(SCHEMA, "storages: []").unwrap();
+
+        let friendship = graph.explain(&String::from("friendship")).unwrap();
+        assert!(!friendship.is_deletable());
+        assert_eq!(friendship.blocked.len(), 1);
+        assert_eq!(friendship.blocked[0].blocked.edge, "user_friend");
+
+        let option = graph.explain(&String::from("option")).unwrap();
+        assert!(!option.is_deletable());
+        assert!(option.blocked[0].reason.contains("by_x_only"));
+    }
+
+    #[test]
+    fn unknown_object() {
+        let graph = crate::read_yamls(SCHEMA, "storages: []").unwrap();
+        assert!(graph.explain(&String::from("nope")).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use yaml_rust::Yaml;
+
+use super::schema::{check_keys, required_str, SchemaProblem};
+use super::DelfGraph;
+use crate::store;
+
+const HOLDS_KEYS: [&str; 1] = ["path"];
+
+/// Where legal holds are kept, from the `holds` section of the config
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoldConfig {
+    /// The json file the holds are kept in
+    pub path: String,
+}
+
+impl HoldConfig {
+    /// Read the `holds` section of a config
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Result<HoldConfig, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(yaml, &HOLDS_KEYS, &mut problems);
+        let path = required_str(yaml, "path", &mut problems);
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(HoldConfig {
+            path: path.unwrap(),
+        })
+    }
+
+    /// All the holds placed, oldest first
+    pub fn holds(&self) -> Result<Vec<Hold>, String> {
+        return store::load(&self.path);
+    }
+
+    /// The (object name, id) of every held instance
+    pub(crate) fn held(&self) -> Result<HashSet<(String, String)>, String> {
+        return Ok(self
+            .holds()?
+            .into_iter()
+            .map(|h| (h.object_type, h.id))
+            .collect());
+    }
+}
+
+/// A legal hold on an object instance, which keeps it from being deleted until the hold is released
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hold {
+    /// The name of the object
+    pub object_type: String,
+    /// The id of the instance
+    pub id: String,
+    /// Why the hold was placed
+    pub reason: Option<String>,
+    /// When the hold was placed, in seconds since the unix epoch
+    pub placed: u64,
+}
+
+/// An object instance a deletion skipped because it is held.  Anything reached only through it is skipped too.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HeldInstance {
+    /// The name of the object
+    pub object: String,
+    /// The id of the instance
+    pub id: String,
+}
+
+impl DelfGraph {
+    fn hold_config(&self) -> Result<&HoldConfig, String> {
+        match &self.holds {
+            Some(config) => Ok(config),
+            None => Err(String::from("No hold store is configured")),
+        }
+    }
+
+    /// All the legal holds placed
+    pub fn holds(&self) -> Result<Vec<Hold>, String> {
+        return self.hold_config()?.holds();
+    }
+
+    /// Place a legal hold on an object instance, so deletions skip it and anything reached only through it.  The instance itself is kept, though edges to it from deleted instances are still removed.  Placing a hold on an instance which is already held returns the existing hold.
+    pub fn place_hold(
+        &self,
+        object_type: &String,
+        id: &String,
+        reason: Option<String>,
+    ) -> Result<Hold, String> {
+        let config = self.hold_config()?;
+        if !self.nodes.contains_key(object_type) {
+            return Err(format!("Unknown object type: {}", object_type));
+        }
+
+        return store::locked(&config.path, || {
+            let mut holds = config.holds()?;
+            if let Some(hold) = holds
+                .iter()
+                .find(|h| h.object_type == *object_type && h.id == *id)
+            {
+                return Ok(hold.clone());
+            }
+
+            let hold = Hold {
+                object_type: object_type.clone(),
+                id: id.clone(),
+                reason,
+                placed: store::now(),
+            };
+            holds.push(hold.clone());
+            store::save(&config.path, &holds)?;
+            return Ok(hold);
+        });
+    }
+
+    /// Release the legal hold on an object instance, returning the hold released
+    pub fn release_hold(&self, object_type: &String, id: &String) -> Result<Hold, String> {
+        let config = self.hold_config()?;
+        return store::locked(&config.path, || {
+            let (released, kept): (Vec<Hold>, Vec<Hold>) = config
+                .holds()?
+                .into_iter()
+                .partition(|h| h.object_type == *object_type && h.id == *id);
+
+            match released.into_iter().next() {
+                Some(hold) => {
+                    store::save(&config.path, &kept)?;
+                    Ok(hold)
+                }
+                None => Err(format!("No hold on {} {}", object_type, id)),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_held_instances() {
+        let path = std::env::temp_dir().join(format!("delf_holds_{}.json", store::new_id()));
+        let config = format!("storages: []\nholds:\n  path: {}\n", path.to_str().unwrap());
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_paper
+      to:
+        object_type: paper
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: paper_review
+      to:
+        object_type: review
+        field: paper_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("paper", &[("id", "10"), ("contact_id", "1")]);
+        memory.insert("paper", &[("id", "11"), ("contact_id", "1")]);
+        memory.insert("review", &[("id", "20"), ("paper_id", "10")]);
+        memory.insert("review", &[("id", "21"), ("paper_id", "11")]);
+
+        let paper = String::from("paper");
+        graph.place_hold(&paper, &String::from("10"), None).unwrap();
+
+        let contact = (String::from("contact"), String::from("1"));
+        let deletion_plan = graph.plan_delete_object(&contact.0, &contact.1).unwrap();
+        let deletion = graph.delete_object(&contact.0, &contact.1).unwrap();
+        let held = vec![HeldInstance {
+            object: paper.clone(),
+            id: String::from("10"),
+        }];
+        assert_eq!(deletion_plan.held, held);
+        assert_eq!(deletion.held, held);
+        assert_eq!(memory.column("paper", "id"), vec!["10"]);
+        assert_eq!(memory.column("review", "id"), vec!["20"]);
+
+        graph.release_hold(&paper, &String::from("10")).unwrap();
+        assert_eq!(graph.holds(), Ok(Vec::new()));
+        assert!(graph.release_hold(&paper, &String::from("10")).is_err());
+
+        // a deletion which can't read the holds is refused before anything is deleted
+        std::fs::write(&path, "not json").unwrap();
+        assert!(graph.delete_object(&paper, &String::from("10")).is_err());
+        assert_eq!(memory.column("paper", "id"), vec!["10"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use yaml_rust::Yaml;
+
+use super::plan::{DeletionPlan, PlannedEdge, PlannedObject};
+use super::schema::{check_keys, optional_u64, SchemaProblem};
+use super::DelfGraph;
+
+const LIMITS_KEYS: [&str; 4] = ["max_instances", "max_depth", "max_rows_per_statement", "objects"];
+const LIMIT_KEYS: [&str; 3] = ["max_instances", "max_depth", "max_rows_per_statement"];
+
+/// Bounds on what one deletion can remove.  Each bound is unlimited if not given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limit {
+    /// The most object instances one deletion can remove
+    pub max_instances: Option<u64>,
+    /// The most edges a cascade can follow from the instance deleted to reach an instance
+    pub max_depth: Option<u64>,
+    /// The most rows one storage statement can change
+    pub max_rows_per_statement: Option<u64>,
+}
+
+impl Limit {
+    fn from_yaml(yaml: &Yaml, known: &[&str], problems: &mut Vec<SchemaProblem>) -> Limit {
+        check_keys(yaml, known, problems);
+        Limit {
+            max_instances: optional_u64(yaml, "max_instances", problems),
+            max_depth: optional_u64(yaml, "max_depth", problems),
+            max_rows_per_statement: optional_u64(yaml, "max_rows_per_statement", problems),
+        }
+    }
+}
+
+/// The blast-radius limits of deletions, from the `limits` section of the config.  Every deletion is planned before anything is removed, and refused if the plan exceeds a limit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Limits on all the instances in a deletion
+    pub global: Limit,
+    /// Limits on the instances of each object in a deletion
+    pub objects: HashMap<String, Limit>,
+}
+
+impl Limits {
+    /// Read the `limits` section of a config
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Result<Limits, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        let global = Limit::from_yaml(yaml, &LIMITS_KEYS, &mut problems);
+
+        let mut objects = HashMap::new();
+        match &yaml["objects"] {
+            Yaml::Hash(hash) => {
+                for (name, limit_yaml) in hash.iter() {
+                    match name.as_str() {
+                        Some(name) => {
+                            let mut object_problems = Vec::new();
+                            let limit = Limit::from_yaml(limit_yaml, &LIMIT_KEYS, &mut object_problems);
+                            problems.extend(
+                                object_problems
+                                    .into_iter()
+                                    .map(|p| p.within(&format!("objects.{}", name))),
+                            );
+                            objects.insert(String::from(name), limit);
+                        }
+                        None => problems.push(SchemaProblem::new(
+                            "objects",
+                            String::from("object names must be strings"),
+                        )),
+                    }
+                }
+            }
+            Yaml::BadValue => (),
+            _ => problems.push(SchemaProblem::new(
+                "objects",
+                String::from("`objects` must be a map of object names to limits"),
+            )),
+        }
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(Limits { global, objects })
+    }
+}
+
+// What a plan would do, measured against the limits
+#[derive(Default)]
+struct Extent {
+    // the number of instances of each object
+    instances: HashMap<String, u64>,
+    // the deepest each object is reached
+    depth: HashMap<String, u64>,
+    // the most rows of each object's table one statement changes
+    rows: HashMap<String, u64>,
+}
+
+impl DelfGraph {
+    /// Check a deletion plan against the configured limits, returning a description of every limit it exceeds.  Always passes if no limits are configured.
+    pub fn check_limits(&self, deletion_plan: &DeletionPlan) -> Result<(), String> {
+        let limits = match &self.limits {
+            Some(limits) => limits,
+            None => return Ok(()),
+        };
+
+        let mut extent = Extent::default();
+        for obj in deletion_plan.objects.iter() {
+            self.measure_object(obj, 0, &mut extent);
+        }
+        for edge in deletion_plan.edges.iter() {
+            self.measure_edge(edge, 0, &mut extent);
+        }
+
+        let mut exceeded = Vec::new();
+        let total: u64 = extent.instances.values().sum();
+        let deepest = extent.depth.values().cloned().max().unwrap_or(0);
+        let most_rows = extent.rows.values().cloned().max().unwrap_or(0);
+        check(&limits.global, "", total, deepest, most_rows, &mut exceeded);
+
+        let mut names: Vec<&String> = limits.objects.keys().collect();
+        names.sort();
+        for name in names.into_iter() {
+            check(
+                &limits.objects[name],
+                &format!(" of {}", name),
+                extent.instances.get(name).cloned().unwrap_or(0),
+                extent.depth.get(name).cloned().unwrap_or(0),
+                extent.rows.get(name).cloned().unwrap_or(0),
+                &mut exceeded,
+            );
+        }
+
+        if exceeded.len() > 0 {
+            return Err(format!(
+                "Deletion refused, it exceeds the configured limits: {}",
+                exceeded.join("; ")
+            ));
+        }
+        return Ok(());
+    }
+
+    fn measure_object(&self, obj: &PlannedObject, depth: u64, extent: &mut Extent) {
+        *extent.instances.entry(obj.object.clone()).or_insert(0) += 1;
+        let deepest = extent.depth.entry(obj.object.clone()).or_insert(0);
+        *deepest = depth.max(*deepest);
+        // deleting the instance is a statement changing one row
+        let rows = extent.rows.entry(obj.object.clone()).or_insert(0);
+        *rows = (*rows).max(1);
+
+        for edge in obj.edges.iter() {
+            self.measure_edge(edge, depth, extent);
+        }
+    }
+
+    fn measure_edge(&self, edge: &PlannedEdge, depth: u64, extent: &mut Extent) {
+        // the edges from an instance are deleted with one statement against the table of the object they point to
+        let to_object = self.get_edge(&edge.edge).to.object_type.clone();
+        let rows = extent.rows.entry(to_object).or_insert(0);
+        *rows = (edge.to_ids.len() as u64).max(*rows);
+
+        for obj in edge.objects.iter() {
+            self.measure_object(obj, depth + 1, extent);
+        }
+        for inverse in edge.inverses.iter() {
+            self.measure_edge(inverse, depth + 1, extent);
+        }
+    }
+}
+
+// Add a description of each bound of the limit which is exceeded
+fn check(limit: &Limit, of: &str, instances: u64, depth: u64, rows: u64, exceeded: &mut Vec<String>) {
+    match limit.max_instances {
+        Some(max) if instances > max => exceeded.push(format!(
+            "{} instance(s){} would be deleted, more than max_instances of {}",
+            instances, of, max
+        )),
+        _ => (),
+    }
+    match limit.max_depth {
+        Some(max) if depth > max => exceeded.push(format!(
+            "the cascade would reach a depth of {}{}, more than max_depth of {}",
+            depth, of, max
+        )),
+        _ => (),
+    }
+    match limit.max_rows_per_statement {
+        Some(max) if rows > max => exceeded.push(format!(
+            "a statement would change {} row(s){}, more than max_rows_per_statement of {}",
+            rows, of, max
+        )),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn refuses_deletions_over_limits() {
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let config = "
+storages: []
+limits:
+  max_depth: 1
+  objects:
+    review:
+      max_instances: 2
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, config);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("contact", &[("id", "2")]);
+        for (id, contact_id) in [("10", "1"), ("11", "1"), ("12", "1"), ("13", "2")].iter() {
+            memory.insert("review", &[("id", id), ("contact_id", contact_id)]);
+        }
+
+        let refused = graph.delete_object(&String::from("contact"), &String::from("1"));
+        assert_eq!(
+            refused,
+            Err(String::from("Deletion refused, it exceeds the configured limits: 3 instance(s) of review would be deleted, more than max_instances of 2"))
+        );
+        assert_eq!(memory.column("review", "id").len(), 4);
+        let edge = graph.get_edge(&String::from("contact_review"));
+        assert!(edge.delete_all(&String::from("1"), &graph).is_err());
+        assert_eq!(memory.column("review", "id").len(), 4);
+
+        assert!(graph.delete_object(&String::from("contact"), &String::from("2")).is_ok());
+        assert_eq!(memory.column("review", "id"), vec!["10", "11", "12"]);
+    }
+}
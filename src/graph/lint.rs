@@ -0,0 +1,591 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use petgraph::visit::EdgeRef;
+use yaml_rust::Yaml;
+
+use super::edge::{DelfEdge, DeleteType as EdgeDeleteType};
+use super::object::{DelfObject, DeleteType as ObjectDeleteType};
+use super::schema::SchemaProblem;
+use super::DelfGraph;
+
+/// How seriously a lint rule's findings are treated by `validate`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// The rule isn't run
+    Off,
+    /// Findings are reported, but validation still passes
+    Warn,
+    /// Findings are reported and validation fails
+    Error,
+}
+
+impl Severity {
+    fn parse(input: &str) -> Option<Severity> {
+        match input.to_lowercase().as_str() {
+            "off" => Some(Severity::Off),
+            "warn" => Some(Severity::Warn),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Off => write!(f, "off"),
+            Severity::Warn => write!(f, "warn"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Something a lint rule found in the graph, reported against the object it was found on (for edges, the object the edge is defined on)
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintFinding {
+    pub object: String,
+    pub edge: Option<String>,
+    pub message: String,
+}
+
+/// A named check run against the graph
+pub struct LintRule {
+    /// The name of the rule, as used in the `lints` configuration
+    pub name: &'static str,
+    /// What the rule checks for
+    pub description: &'static str,
+    /// The severity of the rule if it isn't configured
+    pub default_severity: Severity,
+    check: fn(&DelfGraph) -> Vec<LintFinding>,
+}
+
+/// All of the lint rules known to delf
+pub fn rules() -> Vec<LintRule> {
+    vec![
+        LintRule {
+            name: "mapping_table_inverse",
+            description: "edges through a mapping table must have an inverse edge",
+            default_severity: Severity::Warn,
+            check: check_mapping_table_inverse,
+        },
+        LintRule {
+            name: "deep_into_directly_only",
+            description: "`deep` edges must not point to `directly_only` objects",
+            default_severity: Severity::Warn,
+            check: check_deep_into_directly_only,
+        },
+        LintRule {
+            name: "explicit_id_type",
+            description: "objects must declare their `id_type`",
+            default_severity: Severity::Off,
+            check: check_explicit_id_type,
+        },
+        LintRule {
+            name: "ineffective_edge",
+            description: "`deep` and `refcount` edges must be able to delete the object they point to",
+            default_severity: Severity::Warn,
+            check: check_ineffective_edge,
+        },
+        LintRule {
+            name: "ineffective_object",
+            description: "`by_x_only` objects must only list inbound edges which can delete them",
+            default_severity: Severity::Warn,
+            check: check_ineffective_object,
+        },
+    ]
+}
+
+/// A lint finding along with the rule and severity it was reported with
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintDiagnostic {
+    pub rule: String,
+    pub severity: Severity,
+    pub finding: LintFinding,
+}
+
+impl fmt::Display for LintDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.finding.edge {
+            Some(edge) => write!(
+                f,
+                "[{}] {} ({}.{}): {}",
+                self.severity, self.rule, self.finding.object, edge, self.finding.message
+            ),
+            None => write!(
+                f,
+                "[{}] {} ({}): {}",
+                self.severity, self.rule, self.finding.object, self.finding.message
+            ),
+        }
+    }
+}
+
+// the configured severity of a rule and the objects it is suppressed for
+#[derive(Clone, Debug)]
+struct RuleConfig {
+    severity: Severity,
+    allow: HashSet<String>,
+}
+
+/// The lint configuration, from the `lints` sections of the config and schema.  Each rule can be given a severity directly (`rule_name: warn`), or as a mapping with a `level` and a list of objects to `allow` (suppress) the rule for.
+#[derive(Clone, Debug, Default)]
+pub struct LintConfig {
+    rules: HashMap<String, RuleConfig>,
+}
+
+impl LintConfig {
+    /// Add the rules configured in a `lints` yaml section, overriding any earlier configuration of the same rule.
+    pub(crate) fn merge(&mut self, yaml: &Yaml) -> Result<(), Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        let known: Vec<&str> = rules().iter().map(|r| r.name).collect();
+
+        let hash = match yaml.as_hash() {
+            Some(hash) => hash,
+            None => {
+                return Err(vec![SchemaProblem::new(
+                    "",
+                    String::from("`lints` must be a mapping of rule names to severities"),
+                )])
+            }
+        };
+
+        for (key, value) in hash.iter() {
+            let name = match key.as_str() {
+                Some(name) if known.contains(&name) => name,
+                Some(name) => {
+                    problems.push(SchemaProblem::new(
+                        name,
+                        format!(
+                            "unknown lint rule `{}`, expected one of: {}",
+                            name,
+                            known.join(", ")
+                        ),
+                    ));
+                    continue;
+                }
+                None => {
+                    problems.push(SchemaProblem::new(
+                        "",
+                        format!("lint rule names must be strings, found {:?}", key),
+                    ));
+                    continue;
+                }
+            };
+
+            let (level, allow_yaml) = match value {
+                Yaml::Hash(_) => (value["level"].as_str(), &value["allow"]),
+                _ => (value.as_str(), &Yaml::BadValue),
+            };
+
+            let severity = match level.and_then(Severity::parse) {
+                Some(severity) => severity,
+                None => {
+                    problems.push(SchemaProblem::new(
+                        name,
+                        String::from("lint severity must be one of: off, warn, error"),
+                    ));
+                    continue;
+                }
+            };
+
+            let mut allow = HashSet::new();
+            match allow_yaml {
+                Yaml::BadValue => (),
+                Yaml::Array(objects) => {
+                    for (i, obj) in objects.iter().enumerate() {
+                        match obj.as_str() {
+                            Some(obj_name) => {
+                                allow.insert(String::from(obj_name));
+                            }
+                            None => problems.push(SchemaProblem::new(
+                                &format!("{}.allow[{}]", name, i),
+                                String::from("`allow` must be a list of object names"),
+                            )),
+                        }
+                    }
+                }
+                _ => problems.push(SchemaProblem::new(
+                    &format!("{}.allow", name),
+                    String::from("`allow` must be a list of object names"),
+                )),
+            }
+
+            self.rules
+                .insert(String::from(name), RuleConfig { severity, allow });
+        }
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+        return Ok(());
+    }
+
+    // the configured severity of the rule, or its default
+    fn severity(&self, rule: &LintRule) -> Severity {
+        match self.rules.get(rule.name) {
+            Some(config) => config.severity,
+            None => rule.default_severity,
+        }
+    }
+
+    // whether the rule has been suppressed for the object
+    fn allows(&self, rule: &LintRule, object: &String) -> bool {
+        match self.rules.get(rule.name) {
+            Some(config) => config.allow.contains(object),
+            None => false,
+        }
+    }
+}
+
+impl DelfGraph {
+    /// Run all of the lint rules that aren't turned off against the graph, returning the findings that aren't suppressed.
+    pub fn lint(&self) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for rule in rules().iter() {
+            let severity = self.lints.severity(rule);
+            if severity == Severity::Off {
+                continue;
+            }
+
+            let mut findings = (rule.check)(self);
+            findings.sort_by(|a, b| (&a.object, &a.edge).cmp(&(&b.object, &b.edge)));
+            for finding in findings.drain(..) {
+                if !self.lints.allows(rule, &finding.object) {
+                    diagnostics.push(LintDiagnostic {
+                        rule: String::from(rule.name),
+                        severity,
+                        finding,
+                    });
+                }
+            }
+        }
+
+        return diagnostics;
+    }
+
+    // Call the function with each edge in the graph and the objects it goes from and to
+    pub(crate) fn each_edge<F>(&self, mut f: F)
+    where
+        F: FnMut(&DelfObject, &DelfEdge, &DelfObject),
+    {
+        for e in self.graph.edge_references() {
+            f(&self.graph[e.source()], e.weight(), &self.graph[e.target()]);
+        }
+    }
+}
+
+fn check_mapping_table_inverse(graph: &DelfGraph) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    graph.each_edge(|from, e, _| {
+        if let (Some(table), None) = (&e.to.mapping_table, &e.inverse) {
+            findings.push(LintFinding {
+                object: from.name.clone(),
+                edge: Some(e.name.clone()),
+                message: format!("edge through mapping table {} has no inverse", table),
+            });
+        }
+    });
+    return findings;
+}
+
+fn check_deep_into_directly_only(graph: &DelfGraph) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    graph.each_edge(|from, e, to| {
+        if e.deletion == EdgeDeleteType::Deep && to.deletion == ObjectDeleteType::DirectlyOnly {
+            findings.push(LintFinding {
+                object: from.name.clone(),
+                edge: Some(e.name.clone()),
+                message: format!("`deep` edge points to {}, which is `directly_only`", to.name),
+            });
+        }
+    });
+    return findings;
+}
+
+fn check_explicit_id_type(graph: &DelfGraph) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    for (_, node_id) in graph.nodes.iter() {
+        let obj = graph.graph.node_weight(*node_id).unwrap();
+        if !obj.id_type_declared {
+            findings.push(LintFinding {
+                object: obj.name.clone(),
+                edge: None,
+                message: format!("`id_type` isn't declared, so it defaults to `{}`", obj.id_type),
+            });
+        }
+    }
+    return findings;
+}
+
+// Simulate `DelfObject::delete` for each edge which tries to delete the object it points to
+fn check_ineffective_edge(graph: &DelfGraph) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    graph.each_edge(|from, e, to| {
+        if e.deletion == EdgeDeleteType::Shallow {
+            return;
+        }
+        // `deep_into_directly_only` already reports these
+        if e.deletion == EdgeDeleteType::Deep && to.deletion == ObjectDeleteType::DirectlyOnly {
+            return;
+        }
+
+        if !to.deletable_by(Some(e)) {
+            let reason = match &to.deletion {
+                ObjectDeleteType::ByXOnly(_) => format!(
+                    "{} is `by_x_only` and {} isn't listed in its `x`",
+                    to.name, e.name
+                ),
+                deletion => format!(
+                    "{} is `{}`, so it is never deleted through an edge",
+                    to.name, deletion
+                ),
+            };
+            findings.push(LintFinding {
+                object: from.name.clone(),
+                edge: Some(e.name.clone()),
+                message: format!("`{}` edge has no effect: {}", e.deletion, reason),
+            });
+        } else if e.deletion == EdgeDeleteType::RefCount
+            && e.to.mapping_table.is_none()
+            && e.from.is_none()
+            && e.to.path.is_none()
+            && graph.get_inbound_edges(to).len() == 1
+        {
+            // through a mapping table, from the rows of the objects edges start from, or through a path of join tables, other instances can still refer to the object with the same edge
+            findings.push(LintFinding {
+                object: from.name.clone(),
+                edge: Some(e.name.clone()),
+                message: format!(
+                    "`refcount` edge is the only edge into {}, so it always deletes like a `deep` edge",
+                    to.name
+                ),
+            });
+        }
+    });
+    return findings;
+}
+
+// Check each edge listed by a `by_x_only` object is an inbound edge which tries to delete it
+fn check_ineffective_object(graph: &DelfGraph) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    for (_, node_id) in graph.nodes.iter() {
+        let obj = graph.graph.node_weight(*node_id).unwrap();
+        let x = match &obj.deletion {
+            ObjectDeleteType::ByXOnly(x) => x,
+            _ => continue,
+        };
+
+        let inbound_edges = graph.get_inbound_edges(obj);
+        let mut names: Vec<&String> = x.iter().collect();
+        names.sort();
+
+        let mut effective = false;
+        for name in names.iter() {
+            match inbound_edges.iter().find(|e| &&e.name == name) {
+                Some(e) if e.deletion == EdgeDeleteType::Shallow => findings.push(LintFinding {
+                    object: obj.name.clone(),
+                    edge: None,
+                    message: format!(
+                        "`x` lists {}, which is a `shallow` edge and never deletes {}",
+                        name, obj.name
+                    ),
+                }),
+                Some(_) => effective = true,
+                None => findings.push(LintFinding {
+                    object: obj.name.clone(),
+                    edge: None,
+                    message: format!("`x` lists {}, which isn't an edge into {}", name, obj.name),
+                }),
+            }
+        }
+
+        if !effective {
+            findings.push(LintFinding {
+                object: obj.name.clone(),
+                edge: None,
+                message: format!(
+                    "none of the edges in `x` can delete {}, so it is never deleted",
+                    obj.name
+                ),
+            });
+        }
+    }
+    return findings;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Severity;
+
+    const SCHEMA: &str = "
+object_type:
+  name: user
+  storage: s
+  deletion: directly
+  id: id
+  id_type: number
+  edge_types:
+    - name: user_group
+      to:
+        object_type: group
+        field: user_id
+        mapping_table: user_groups
+      deletion: shallow
+---
+object_type:
+  name: group
+  storage: s
+  deletion: directly_only
+  id: id
+  edge_types:
+    - name: group_user
+      to:
+        object_type: user
+        field: group_id
+      deletion: deep
+";
+
+    #[test]
+    fn default_severities() {
+        let graph = crate::read_yamls(SCHEMA, "storages: []").unwrap();
+        let diagnostics = graph.lint();
+
+        let rules: Vec<&str> = diagnostics.iter().map(|d| d.rule.as_str()).collect();
+        assert_eq!(rules, vec!["mapping_table_inverse"]);
+        assert_eq!(diagnostics[0].severity, Severity::Warn);
+    }
+
+    #[test]
+    fn ineffective_edges_and_objects() {
+        let schema = "
+object_type:
+  name: paper
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: paper_request
+      to:
+        object_type: request
+        field: paper_id
+      deletion: deep
+    - name: paper_log
+      to:
+        object_type: log
+        field: paper_id
+      deletion: refcount
+---
+object_type:
+  name: request
+  storage: s
+  deletion: by_x_only
+  x:
+    - paper
+  id: id
+  edge_types: []
+---
+object_type:
+  name: log
+  storage: s
+  deletion: not_deleted
+  id: id
+  edge_types: []
+";
+        let config = "storages: []\nlints:\n  mapping_table_inverse: off\n";
+        let graph = crate::read_yamls(schema, config).unwrap();
+        let messages: Vec<(String, String)> = graph
+            .lint()
+            .into_iter()
+            .map(|d| (d.rule, d.finding.message))
+            .collect();
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].0, "ineffective_edge");
+        assert!(messages[0].1.contains("log is `not_deleted`"));
+        assert!(messages[1].1.contains("request is `by_x_only`"));
+        assert_eq!(messages[2].0, "ineffective_object");
+        assert!(messages[2].1.contains("`x` lists paper, which isn't an edge"));
+        assert!(messages[3].1.contains("never deleted"));
+    }
+
+    #[test]
+    fn refcount_edge_only_flagged_as_the_only_edge() {
+        let schema = "
+object_type:
+  name: paper
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: paper_topic
+      to:
+        object_type: topic
+        field: paper_id
+      deletion: refcount
+---
+object_type:
+  name: user
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: user_topic
+      to:
+        object_type: topic
+        field: user_id
+      deletion: shallow
+---
+object_type:
+  name: topic
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let config = "storages: []\nlints:\n  explicit_id_type: off\n";
+        // a `shallow` edge still keeps the topic from being deleted
+        let graph = crate::read_yamls(schema, config).unwrap();
+        assert!(graph.lint().is_empty());
+
+        // with the user's edge pointing elsewhere, the `refcount` edge is the only one into the topic
+        let schema = schema.replace("object_type: topic\n        field: user_id", "object_type: paper\n        field: user_id");
+        let graph = crate::read_yamls(&schema, config).unwrap();
+        let diagnostics = graph.lint();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].finding.edge, Some(String::from("paper_topic")));
+        assert!(diagnostics[0].finding.message.contains("only edge into topic"));
+    }
+
+    #[test]
+    fn configured_severities_and_suppressions() {
+        let config = "
+storages: []
+lints:
+  mapping_table_inverse: error
+  explicit_id_type:
+    level: warn
+    allow:
+      - user
+";
+        let schema = format!(
+            "{}---\nlints:\n  deep_into_directly_only: off\n  mapping_table_inverse:\n    level: warn\n    allow: [user]\n",
+            SCHEMA
+        );
+        let graph = crate::read_yamls(&schema, config).unwrap();
+        let diagnostics = graph.lint();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "explicit_id_type");
+        assert_eq!(diagnostics[0].finding.object, "group");
+    }
+
+    #[test]
+    fn unknown_rules_are_schema_problems() {
+        let config = "storages: []\nlints:\n  not_a_rule: warn\n  explicit_id_type: sometimes\n";
+        let err = crate::read_yamls(SCHEMA, config).unwrap_err();
+        assert_eq!(err.problems.len(), 2);
+        assert_eq!(err.problems[0].path, "lints.not_a_rule");
+        assert_eq!(err.problems[0].line, 3);
+    }
+}
@@ -1,19 +1,61 @@
 use std::collections::{HashMap, HashSet};
-use std::process::exit;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
 
-use ansi_term::Colour::{Red, Green, Cyan};
+use ansi_term::Colour::{Red, Green, Cyan, Yellow};
 use petgraph::{
     graph::{EdgeIndex, NodeIndex},
     Directed, Graph, Incoming, Outgoing,
 };
-
+use serde::Serialize;
+use yaml_rust::Yaml;
+
+/// Deleting every instance of an object which matches a predicate
+pub mod bulk;
+mod cascade;
+/// Checkpoints of unfinished cascades, so they can be resumed
+pub mod checkpoint;
+/// Detection of `deep` and `refcount` cycles in a DelfGraph
+pub mod cycle;
 /// The edge of a DelfGraph is a DelfEdge
 pub mod edge;
+/// Explanations of how (or why not) an object in a DelfGraph gets deleted
+pub mod explain;
+/// Legal holds, which keep object instances from being deleted
+pub mod hold;
+/// Blast-radius limits on what one deletion can remove
+pub mod limits;
+/// Configurable lint rules run against a DelfGraph
+pub mod lint;
 /// The node of a DelfGraph is a DelfObject
 pub mod object;
-
-use crate::storage::{get_connection, DelfStorageConnection};
+/// Running the edges of a cascade on a pool of threads, within each storage's concurrency limit
+pub(crate) mod parallel;
+/// Dry-run plans of what a deletion would remove
+pub mod plan;
+/// The expression language of `where` predicates on edges
+pub mod predicate;
+/// Logging deleted rows so deletions can be restored
+pub mod restore;
+/// Deletions scheduled for a later time, which can be cancelled until they are due
+pub mod schedule;
+/// Loading a DelF schema, with the location of any problems found in it
+pub mod schema;
+
+use crate::storage::{get_connection, has_plugin, DelfStorageConnection};
+use crate::jobs::JobConfig;
 use crate::DelfYamls;
+use cascade::{Cascade, CascadeState};
+use schema::{optional_str, optional_u64, required_str, SchemaError, SchemaProblem};
+
+/// What a deletion removed
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Deletion {
+    /// The id to restore the deletion with, if a restoration log is configured and anything was deleted
+    pub deletion_id: Option<String>,
+    /// The instances skipped because they are under a legal hold.  Anything reached only through them was skipped too.
+    pub held: Vec<hold::HeldInstance>,
+}
 
 /// The DelfGraph is the core structure for delf's functionality.  It contains the algorithm to traverse the graph, as well as metadata to perform the deletions.
 #[derive(Debug)]
@@ -22,68 +64,331 @@ pub struct DelfGraph {
     pub(crate) edges: HashMap<String, EdgeIndex>,
     graph: Graph<object::DelfObject, edge::DelfEdge, Directed>,
     storages: HashMap<String, Box<dyn DelfStorageConnection>>,
+    // the (plugin, url) each storage was connected with
+    storage_urls: HashMap<String, (String, String)>,
+    // the number of cascade tasks which can run against each storage at once
+    concurrency: HashMap<String, usize>,
+    // the storages whose instances are removed after everything referring to them
+    child_first: HashSet<String>,
+    lints: lint::LintConfig,
+    restoration: Option<restore::RestorationLog>,
+    limits: Option<limits::Limits>,
+    holds: Option<hold::HoldConfig>,
+    schedules: Option<schedule::ScheduleConfig>,
+    pub(crate) jobs: Option<JobConfig>,
+    pub(crate) checkpoints: Option<checkpoint::CheckpointConfig>,
 }
 
 impl DelfGraph {
-    /// Create a new DelfGraph from a schema and a config.  See [yaml_rust](../../yaml_rust/index.html) for information on creating the Yaml structs, or alternately use the helper functions: [read_files](../fn.read_files.html), [read_yamls](../fn.read_yamls.html) for constructing a DelfGraph from either paths or `&str` of yaml.
-    pub fn new(yamls: &DelfYamls) -> DelfGraph {
+    /// Create a new DelfGraph from a schema and a config.  See [yaml_rust](../../yaml_rust/index.html) for information on creating the Yaml structs, or alternately use the helper functions: [read_files](../fn.read_files.html), [read_yamls](../fn.read_yamls.html) for constructing a DelfGraph from either paths or `&str` of yaml.  If the schema has any problems, they are all returned together in a [SchemaError](schema/struct.SchemaError.html).
+    pub fn new(yamls: &DelfYamls) -> Result<DelfGraph, SchemaError> {
         let schema = &yamls.schema;
         let config = &yamls.config;
+        let mut problems = Vec::new();
         let mut edges_to_insert = Vec::new();
         let mut nodes = HashMap::<String, NodeIndex>::new();
         let mut edges = HashMap::<String, EdgeIndex>::new();
 
         let mut graph = Graph::<object::DelfObject, edge::DelfEdge>::new();
 
-        // each yaml is an object
-        for yaml in schema.iter() {
-            let obj_name = String::from(yaml["object_type"]["name"].as_str().unwrap());
-            let obj_node = object::DelfObject::from(&yaml["object_type"]);
+        // lints configured in the config are overridden by those in the schema
+        let mut lints = lint::LintConfig::default();
+        for (doc, yaml) in config.iter().enumerate() {
+            if !yaml["lints"].is_badvalue() {
+                if let Err(e) = lints.merge(&yaml["lints"]) {
+                    for mut problem in e.into_iter().map(|p| p.within("lints")) {
+                        problem.message = format!("in config: {}", problem.message);
+                        problems.push(yamls.config_markers.locate(doc, problem));
+                    }
+                }
+            }
+        }
 
-            let node_id = graph.add_node(obj_node);
-            nodes.insert(obj_name.clone(), node_id);
+        let restoration = config_section(
+            yamls,
+            "restoration",
+            restore::RestorationLog::from_yaml,
+            &mut problems,
+        );
+        let jobs = config_section(yamls, "jobs", JobConfig::from_yaml, &mut problems);
+        let limits = config_section(yamls, "limits", limits::Limits::from_yaml, &mut problems);
+        let holds = config_section(yamls, "holds", hold::HoldConfig::from_yaml, &mut problems);
+        let schedules = config_section(
+            yamls,
+            "schedules",
+            schedule::ScheduleConfig::from_yaml,
+            &mut problems,
+        );
+
+        // the (name, plugin, url) of each storage, the storages which can run more than one cascade task at once, and those deleted child-first
+        let mut storage_configs = Vec::new();
+        let mut concurrency = HashMap::new();
+        let mut child_first = HashSet::new();
+        let mut has_storages = false;
+        for (doc, yaml) in config.iter().enumerate() {
+            let entries = match &yaml["storages"] {
+                Yaml::BadValue => continue,
+                Yaml::Array(entries) => entries,
+                _ => {
+                    let problem = SchemaProblem::new(
+                        "storages",
+                        String::from("in config: `storages` must be a list"),
+                    );
+                    problems.push(yamls.config_markers.locate(doc, problem));
+                    continue;
+                }
+            };
+            has_storages = true;
+            for (i, storage) in entries.iter().enumerate() {
+                let mut storage_problems = Vec::new();
+                let name = required_str(storage, "name", &mut storage_problems);
+                let plugin = required_str(storage, "plugin", &mut storage_problems);
+                let url = required_str(storage, "url", &mut storage_problems);
+                if let Some(p) = &plugin {
+                    if !has_plugin(p) {
+                        storage_problems.push(SchemaProblem::new(
+                            "plugin",
+                            format!("unknown storage plugin `{}`", p),
+                        ));
+                    }
+                }
+                let limit = optional_u64(storage, "concurrency", &mut storage_problems).unwrap_or(1);
+                if limit == 0 {
+                    storage_problems.push(SchemaProblem::new(
+                        "concurrency",
+                        String::from("`concurrency` must be at least 1"),
+                    ));
+                }
+                let order = optional_str(storage, "order", &mut storage_problems);
+                match order.as_deref() {
+                    None | Some("parent_first") => (),
+                    Some("child_first") => {
+                        if let Some(name) = &name {
+                            child_first.insert(name.clone());
+                        }
+                    }
+                    Some(o) => storage_problems.push(SchemaProblem::new(
+                        "order",
+                        format!("unknown order `{}`, expected one of: parent_first, child_first", o),
+                    )),
+                }
+                let path = format!("storages[{}]", i);
+                for mut problem in storage_problems.into_iter().map(|p| p.within(&path)) {
+                    problem.message = format!("in config: {}", problem.message);
+                    problems.push(yamls.config_markers.locate(doc, problem));
+                }
+                if let (Some(name), Some(plugin), Some(url)) = (name, plugin, url) {
+                    concurrency.insert(name.clone(), limit as usize);
+                    storage_configs.push((name, plugin, url));
+                }
+            }
+        }
+        if !has_storages {
+            let problem = SchemaProblem::new(
+                "storages",
+                String::from("in config: missing required key `storages`"),
+            );
+            problems.push(yamls.config_markers.locate(0, problem));
+        }
+        let checkpoints = config_section(
+            yamls,
+            "checkpoints",
+            checkpoint::CheckpointConfig::from_yaml,
+            &mut problems,
+        );
+        // a failed job is retried from the checkpoint of its cascade
+        if jobs.is_some() && checkpoints.is_none() {
+            let doc = yamls.config.iter().position(|y| !y["jobs"].is_badvalue()).unwrap_or(0);
+            let problem = SchemaProblem::new(
+                "jobs",
+                String::from("in config: a job queue needs `checkpoints` configured to retry failed jobs from"),
+            );
+            problems.push(yamls.config_markers.locate(doc, problem));
+        }
+
+        // each yaml is an object, or a `lints` section
+        for (doc, yaml) in schema.iter().enumerate() {
+            let mut doc_problems = Vec::new();
+            schema::check_keys(yaml, &["object_type", "lints"], &mut doc_problems);
+
+            if !yaml["lints"].is_badvalue() {
+                if let Err(e) = lints.merge(&yaml["lints"]) {
+                    doc_problems.extend(e.into_iter().map(|p| p.within("lints")));
+                }
+                if yaml["object_type"].is_badvalue() {
+                    for problem in doc_problems.drain(..) {
+                        problems.push(yamls.schema_markers.locate(doc, problem));
+                    }
+                    continue;
+                }
+            }
+
+            let obj_yaml = &yaml["object_type"];
+            let obj_name = match object::DelfObject::try_from(obj_yaml) {
+                Ok(obj_node) => {
+                    let obj_name = obj_node.name.clone();
+                    if nodes.contains_key(&obj_name) {
+                        doc_problems.push(SchemaProblem::new(
+                            "object_type.name",
+                            format!("an object named {:#?} already exists", obj_name),
+                        ));
+                    } else {
+                        let node_id = graph.add_node(obj_node);
+                        nodes.insert(obj_name.clone(), node_id);
+                    }
+                    Some(obj_name)
+                }
+                Err(e) => {
+                    doc_problems.extend(e.into_iter().map(|p| p.within("object_type")));
+                    None
+                }
+            };
 
             // need to make sure all the nodes exist before edges can be added to the graph
-            for e in yaml["object_type"]["edge_types"].as_vec().unwrap().iter() {
-                let delf_edge = edge::DelfEdge::from(e);
-                edges_to_insert.push((obj_name.clone(), delf_edge));
+            match obj_yaml["edge_types"].as_vec() {
+                Some(edge_yamls) => {
+                    for (i, e) in edge_yamls.iter().enumerate() {
+                        let path = format!("object_type.edge_types[{}]", i);
+                        match (edge::DelfEdge::try_from(e), &obj_name) {
+                            (Ok(delf_edge), Some(name)) => {
+                                edges_to_insert.push((name.clone(), doc, path, delf_edge));
+                            }
+                            (Ok(_), None) => (),
+                            (Err(e), _) => {
+                                doc_problems.extend(e.into_iter().map(|p| p.within(&path)));
+                            }
+                        }
+                    }
+                }
+                None => doc_problems.push(SchemaProblem::new(
+                    "object_type.edge_types",
+                    String::from("`edge_types` must be a list of edges (use `[]` if there are none)"),
+                )),
+            }
+
+            for problem in doc_problems.drain(..) {
+                problems.push(yamls.schema_markers.locate(doc, problem));
             }
         }
 
         // add all the edges to the graph
-        for (from, e) in edges_to_insert.iter_mut() {
+        for (from, doc, path, e) in edges_to_insert.iter() {
             if !nodes.contains_key(&e.to.object_type) {
-                eprintln!("Error creating edge {:#?}: No object with name {:#?}", e.name, e.to.object_type);
-                exit(1);
+                let problem = SchemaProblem::new(
+                    "to.object_type",
+                    format!("No object with name {:#?}", e.to.object_type),
+                );
+                problems.push(yamls.schema_markers.locate(*doc, problem.within(path)));
+                continue;
+            }
+            if edges.contains_key(&e.name) {
+                let problem = SchemaProblem::new(
+                    "name",
+                    format!("an edge named {:#?} already exists", e.name),
+                );
+                problems.push(yamls.schema_markers.locate(*doc, problem.within(path)));
+                continue;
+            }
+            let mut e = e.clone();
+            if let Some(from_type) = e.from.as_mut() {
+                // an edge stored on its source holds the id of the object it points to
+                from_type.object_type = from.clone();
+                e.to.field = graph[nodes[&e.to.object_type]].id_field.clone();
             }
             let edge_id = graph.add_edge(nodes[from], nodes[&e.to.object_type], e.clone());
             edges.insert(String::from(&e.name), edge_id);
         }
 
+        // inverses can point at any edge in the schema, so check them once all edges are known
+        for (_, doc, path, e) in edges_to_insert.iter() {
+            match &e.inverse {
+                Some(inverse) if !edges.contains_key(inverse) => {
+                    let problem = SchemaProblem::new(
+                        "inverse",
+                        format!("No edge with name {:#?}", inverse),
+                    );
+                    problems.push(yamls.schema_markers.locate(*doc, problem.within(path)));
+                }
+                _ => (),
+            }
+        }
+
+        // limits can be given for any object in the schema, so check them once all objects are known
+        for (doc, yaml) in config.iter().enumerate() {
+            if let Some(hash) = yaml["limits"]["objects"].as_hash() {
+                for name in hash.keys().filter_map(|k| k.as_str()) {
+                    if !nodes.contains_key(name) {
+                        let problem = SchemaProblem::new(
+                            name,
+                            format!("in config: No object with name {:#?}", name),
+                        );
+                        problems.push(yamls.config_markers.locate(doc, problem.within("limits.objects")));
+                    }
+                }
+            }
+        }
+
+        if problems.len() > 0 {
+            return Err(SchemaError { problems });
+        }
+
         // create the storage map
         let mut storages = HashMap::<String, Box<dyn DelfStorageConnection>>::new();
+        let mut storage_urls = HashMap::new();
 
-        for yaml in config.iter() {
-            for storage in yaml["storages"].as_vec().unwrap().iter() {
-                let storage_name = String::from(storage["name"].as_str().unwrap());
-                storages.insert(
-                    storage_name,
-                    get_connection(
-                        storage["plugin"].as_str().unwrap(),
-                        storage["url"].as_str().unwrap(),
-                    ),
-                );
-            }
+        for (name, plugin, url) in storage_configs.into_iter() {
+            storages.insert(name.clone(), get_connection(&plugin, &url));
+            storage_urls.insert(name, (plugin, url));
         }
 
-        return DelfGraph {
+        return Ok(DelfGraph {
             nodes,
             edges,
             graph,
             storages,
+            storage_urls,
+            concurrency,
+            child_first,
+            lints,
+            restoration,
+            limits,
+            holds,
+            schedules,
+            jobs,
+            checkpoints,
+        });
+    }
+
+    // Copy the graph with new connections to each storage, for use on another thread
+    pub(crate) fn reconnect(&self) -> DelfGraph {
+        let mut storages = HashMap::<String, Box<dyn DelfStorageConnection>>::new();
+        for (name, (plugin, url)) in self.storage_urls.iter() {
+            storages.insert(name.clone(), get_connection(plugin, url));
+        }
+
+        return DelfGraph {
+            nodes: self.nodes.clone(),
+            edges: self.edges.clone(),
+            graph: self.graph.clone(),
+            storages,
+            storage_urls: self.storage_urls.clone(),
+            concurrency: self.concurrency.clone(),
+            child_first: self.child_first.clone(),
+            lints: self.lints.clone(),
+            restoration: self.restoration.clone(),
+            limits: self.limits.clone(),
+            holds: self.holds.clone(),
+            schedules: self.schedules.clone(),
+            jobs: self.jobs.clone(),
+            checkpoints: self.checkpoints.clone(),
         };
     }
 
+    /// Whether the object's storage removes instances after everything referring to them, for databases with foreign key constraints
+    pub(crate) fn is_child_first(&self, obj: &object::DelfObject) -> bool {
+        return self.child_first.contains(&obj.storage);
+    }
+
     /// Pretty print the graph's contents.
     pub fn print(&self) {
         println!("{:#?}", self.graph);
@@ -95,10 +400,46 @@ impl DelfGraph {
         return self.graph.edge_weight(*edge_id).unwrap();
     }
 
-    /// Given an edge name and the ids of the to/from object instances, delete the edge
-    pub fn delete_edge(&self, edge_name: &String, from_id: &String, to_id: &String) {
+    // Given an edge, get the DelfObject the edge starts from
+    pub(crate) fn get_edge_source(&self, edge: &edge::DelfEdge) -> &object::DelfObject {
+        let edge_id = self.edges.get(&edge.name).unwrap();
+        let (from_id, _) = self.graph.edge_endpoints(*edge_id).unwrap();
+        return self.graph.node_weight(from_id).unwrap();
+    }
+
+    /// Given an edge name and the ids of the to/from object instances, delete the edge, unless the deletion exceeds the configured limits.  Returns what was deleted.
+    pub fn delete_edge(
+        &self,
+        edge_name: &String,
+        from_id: &String,
+        to_id: &String,
+    ) -> Result<Deletion, String> {
+        let task = checkpoint::Task::EdgeOne {
+            edge: edge_name.clone(),
+            from_id: from_id.clone(),
+            to_id: to_id.clone(),
+        };
+        return self.delete_task(task, None);
+    }
+
+    /// Check whether the instance of the edge from `from_id` to `to_id` exists
+    pub fn edge_exists(&self, edge_name: &String, from_id: &String, to_id: &String) -> Result<bool, String> {
+        if !self.edges.contains_key(edge_name) {
+            return Ok(false);
+        }
         let e = self.get_edge(edge_name);
-        e.delete_one(from_id, to_id, self);
+        if self.check_edge_ids(e, from_id, to_id).is_err() {
+            return Ok(false);
+        }
+        let from_obj = self.get_edge_source(e);
+        let cascade = Cascade::new(self, true)?;
+        return Ok(cascade.get_object_ids(e, from_id, &from_obj.id_type).contains(to_id));
+    }
+
+    // Check the ids of an edge instance are of the id types of the objects it connects
+    fn check_edge_ids(&self, e: &edge::DelfEdge, from_id: &String, to_id: &String) -> Result<(), String> {
+        edge::check_id(&self.get_edge_source(e).id_type, from_id)?;
+        return edge::check_id(&self.get_object(&e.to.object_type).id_type, to_id);
     }
 
     /// Given an object name, get the corresponding DelfObject
@@ -107,30 +448,117 @@ impl DelfGraph {
         return self.graph.node_weight(*object_id).unwrap();
     }
 
-    /// Given the object name and the id of the instance, delete the object
-    pub fn delete_object(&self, object_name: &String, id: &String) {
-        self._delete_object(object_name, id, None);
+    /// Given the object name and the id of the instance, delete the object, unless the deletion exceeds the configured limits.  Returns what was deleted.
+    pub fn delete_object(&self, object_name: &String, id: &String) -> Result<Deletion, String> {
+        let task = checkpoint::Task::Object {
+            object: object_name.clone(),
+            id: id.clone(),
+        };
+        return self.delete_task(task, None);
+    }
+
+    // Delete the object instance with its cascade, without checking the limits
+    fn run_delete_object(&self, object_name: &String, id: &String) -> Result<Deletion, String> {
+        let task = checkpoint::Task::Object {
+            object: object_name.clone(),
+            id: id.clone(),
+        };
+        return self.run_task(task, None);
+    }
+
+    /// Delete the object or edge instance of the task with its cascade, checkpointed under the id if given, once the plan is checked against the limits
+    pub(crate) fn delete_task(&self, task: checkpoint::Task, checkpoint_id: Option<&String>) -> Result<Deletion, String> {
+        match &task {
+            checkpoint::Task::Object { object, id } if self.nodes.contains_key(object) => {
+                edge::check_id(&self.get_object(object).id_type, id)?
+            }
+            checkpoint::Task::EdgeOne { edge, from_id, to_id } if self.edges.contains_key(edge) => {
+                self.check_edge_ids(self.get_edge(edge), from_id, to_id)?
+            }
+            checkpoint::Task::EdgeAll { edge, from_id } if self.edges.contains_key(edge) => {
+                edge::check_id(&self.get_edge_source(self.get_edge(edge)).id_type, from_id)?
+            }
+            _ => (),
+        }
+        if self.limits.is_some() {
+            let plan = match &task {
+                checkpoint::Task::Object { object, id } => self.plan_delete_object(object, id)?,
+                checkpoint::Task::EdgeOne { edge, from_id, to_id } => self.plan_delete_edge(edge, from_id, to_id)?,
+                checkpoint::Task::EdgeAll { edge, from_id } => self.plan_delete_edges(edge, from_id)?,
+                _ => plan::DeletionPlan::default(),
+            };
+            self.check_limits(&plan)?;
+        }
+        return self.run_task(task, checkpoint_id);
+    }
+
+    // Run the task with its cascade, checkpointed under the id if given, without checking the limits
+    fn run_task(&self, task: checkpoint::Task, checkpoint_id: Option<&String>) -> Result<Deletion, String> {
+        let state = Arc::new(Mutex::new(CascadeState::new(self, false, checkpoint_id)?));
+        if self.is_parallel() {
+            return self.run_parallel(task, state);
+        }
+        let mut cascade = Cascade::with_state(self, false, state, None);
+        cascade.run(task)?;
+        return Ok(cascade.deletion());
     }
 
-    fn _delete_object(
+    /// Given the object name and the id of the instance, plan the deletion of the object without deleting anything.  The plan contains every object and edge instance the deletion would remove, found using only reads against the storages.
+    pub fn plan_delete_object(
         &self,
         object_name: &String,
         id: &String,
-        from_edge: Option<&edge::DelfEdge>,
-    ) {
-        let obj = self.get_object(object_name);
+    ) -> Result<plan::DeletionPlan, String> {
+        if !self.nodes.contains_key(object_name) {
+            return Err(format!("Unknown object type: {}", object_name));
+        }
+        edge::check_id(&self.get_object(object_name).id_type, id)?;
+        let mut deletion_plan = plan::DeletionPlan::default();
+        let mut cascade = Cascade::new(self, true)?;
+        if let Some(obj) = cascade.delete_object(object_name, id, None) {
+            deletion_plan.objects.push(obj);
+        }
+        deletion_plan.held = cascade.held();
+        return Ok(deletion_plan);
+    }
 
-        let deleted = obj.delete(id, from_edge, &self.storages);
+    /// Given an edge name and the ids of the to/from object instances, plan the deletion of the edge without deleting anything.  The plan contains every object and edge instance the deletion would remove, found using only reads against the storages.
+    pub fn plan_delete_edge(
+        &self,
+        edge_name: &String,
+        from_id: &String,
+        to_id: &String,
+    ) -> Result<plan::DeletionPlan, String> {
+        if !self.edges.contains_key(edge_name) {
+            return Err(format!("Unknown edge type: {}", edge_name));
+        }
+        let mut deletion_plan = plan::DeletionPlan::default();
+        let e = self.get_edge(edge_name);
+        self.check_edge_ids(e, from_id, to_id)?;
+        let mut cascade = Cascade::new(self, true)?;
+        if let Some(edge) = cascade.delete_one(e, from_id, to_id) {
+            deletion_plan.edges.push(edge);
+        }
+        deletion_plan.held = cascade.held();
+        return Ok(deletion_plan);
+    }
 
-        if deleted {
-            let edges = self.graph.edges_directed(self.nodes[&obj.name], Outgoing);
-            for e in edges {
-                e.weight().delete_all(id, &obj.id_type, self);
-            }
+    // Plan the deletion of all edges of a type from an object instance, without deleting anything
+    fn plan_delete_edges(&self, edge_name: &String, from_id: &String) -> Result<plan::DeletionPlan, String> {
+        if !self.edges.contains_key(edge_name) {
+            return Err(format!("Unknown edge type: {}", edge_name));
+        }
+        let mut deletion_plan = plan::DeletionPlan::default();
+        let e = self.get_edge(edge_name);
+        let mut cascade = Cascade::new(self, true)?;
+        if let Some(edge) = cascade.delete_all(e, from_id, &self.get_edge_source(e).id_type) {
+            deletion_plan.edges.push(edge);
         }
+        deletion_plan.held = cascade.held();
+        return Ok(deletion_plan);
     }
 
-    /// Validate that the objects and edges described in the schema exist in the corresponding storage as expected.  Additionally, ensure that all objects in the graph are reachable by traversal via `deep` or `refcount` edges starting at an object with deletion type of `directly`, `directly_only`, `short_ttl`, or `not_deleted`.  This ensures that all objects are deletable and accounted for.
+    /// Validate that the objects and edges described in the schema exist in the corresponding storage as expected.  Additionally, ensure that all objects in the graph are reachable by traversal via `deep` or `refcount` edges starting at an object with deletion type of `directly`, `directly_only`, `short_ttl`, or `not_deleted`.  This ensures that all objects are deletable and accounted for.  Then, check that there are no cycles of `deep` edges or cycles containing `refcount` edges which would prevent a cascade from completing.  Finally, run the configured [lint rules](lint/fn.rules.html), failing validation if any rule with a severity of `error` has findings.
     pub fn validate(&self) {
         println!("\u{1f50d} {}", Cyan.bold().paint("Validating DelF graph..."));
 
@@ -188,6 +616,41 @@ impl DelfGraph {
             println!("\u{2705} {}", Green.paint("All objects deletable"));
         }
 
+        match self.cycle_analysis() {
+            Err(mut e) => errs.append(&mut e),
+            _ => ()
+        }
+
+        if errs.len() > 0 {
+            passed = false;
+            println!("\u{274c} {}", Red.paint("Deletion cycles found"));
+            for err in errs.drain(..) {
+                println!("  {}", err);
+            }
+        } else {
+            println!("\u{2705} {}", Green.paint("No deletion cycles"));
+        }
+
+        let mut lint_errors = 0;
+        let diagnostics = self.lint();
+        for diagnostic in diagnostics.iter() {
+            if diagnostic.severity == lint::Severity::Error {
+                lint_errors += 1;
+            }
+        }
+
+        if lint_errors > 0 {
+            passed = false;
+            println!("\u{274c} {}", Red.paint("Lint errors found"));
+        } else if diagnostics.len() > 0 {
+            println!("\u{26a0} {}", Yellow.paint("Lint warnings found"));
+        } else {
+            println!("\u{2705} {}", Green.paint("No lint findings"));
+        }
+        for diagnostic in diagnostics.iter() {
+            println!("  {}", diagnostic);
+        }
+
         if passed {
             println!("\u{1F680} {} \u{1F680}", Green.bold().paint("Validation successful!"));
         } else {
@@ -215,7 +678,7 @@ impl DelfGraph {
         if visited_nodes.len() != self.nodes.len() {
             let node_set: HashSet<String> = self.nodes.keys().cloned().collect();
             return Err(format!(
-                "Not all objects are deletable: {:?}",
+                "Not all objects are deletable: {:?} (run `delf explain <object_type>` for details)",
                 node_set.difference(&visited_nodes)
             ));
         } else {
@@ -259,8 +722,86 @@ impl DelfGraph {
             let obj = self.graph.node_weight(*node_id).unwrap();
 
             for obj_id in obj.check_short_ttl(&self.storages).iter() {
-                self.delete_object(&obj.name, obj_id);
+                if let Err(e) = self.delete_object(&obj.name, obj_id) {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Purge the instances of soft deleted objects which were marked deleted longer ago than their grace period, except those under a legal hold.  The cascade stopped at each instance when it was marked, so it carries on from the instance now, before the instance is removed.  Returns the number of instances purged.
+    pub fn purge_soft_deleted(&self) -> Result<usize, String> {
+        let held = match &self.holds {
+            Some(config) => config.held()?,
+            None => HashSet::new(),
+        };
+
+        let mut purged = 0;
+        for (_, node_id) in self.nodes.iter() {
+            let obj = self.graph.node_weight(*node_id).unwrap();
+
+            for obj_id in obj.check_purge(&self.storages).iter() {
+                if held.contains(&(obj.name.clone(), obj_id.clone())) {
+                    continue;
+                }
+                let task = checkpoint::Task::Purge {
+                    object: obj.name.clone(),
+                    id: obj_id.clone(),
+                };
+                let mut cascade = Cascade::new(self, false)?;
+                cascade.push_tasks(&[task.clone()])?;
+                if cascade.purge(obj, obj_id) {
+                    purged += 1;
+                }
+                cascade.failure()?;
+                cascade.pop_tasks(&[task]);
+                cascade.finish();
             }
         }
+        return Ok(purged);
     }
 }
+
+// Parse an optional section of the config, locating any problems found in it
+fn config_section<T>(
+    yamls: &DelfYamls,
+    key: &str,
+    parse: fn(&Yaml) -> Result<T, Vec<SchemaProblem>>,
+    problems: &mut Vec<SchemaProblem>,
+) -> Option<T> {
+    let mut section = None;
+    for (doc, yaml) in yamls.config.iter().enumerate() {
+        if !yaml[key].is_badvalue() {
+            match parse(&yaml[key]) {
+                Ok(parsed) => section = Some(parsed),
+                Err(e) => {
+                    for mut problem in e.into_iter().map(|p| p.within(key)) {
+                        problem.message = format!("in config: {}", problem.message);
+                        problems.push(yamls.config_markers.locate(doc, problem));
+                    }
+                }
+            }
+        }
+    }
+    return section;
+}
+
+// Build a graph from the schema with all objects stored in an in-memory storage named `s`
+#[cfg(test)]
+pub(crate) fn test_graph(schema: &str) -> (DelfGraph, crate::storage::memory::MemoryConnection) {
+    return test_graph_with_config(schema, "storages: []");
+}
+
+// Build a graph from the schema and config, with an in-memory storage named `s` added
+#[cfg(test)]
+pub(crate) fn test_graph_with_config(
+    schema: &str,
+    config: &str,
+) -> (DelfGraph, crate::storage::memory::MemoryConnection) {
+    let mut graph = crate::read_yamls(schema, config).unwrap();
+    let memory = crate::storage::memory::MemoryConnection::default();
+    graph
+        .storages
+        .insert(String::from("s"), Box::new(memory.clone()));
+    return (graph, memory);
+}
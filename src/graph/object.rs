@@ -1,12 +1,19 @@
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
 
 use yaml_rust::Yaml;
 
 use super::edge::DelfEdge;
+use super::predicate::Predicate;
+use super::schema::{check_keys, optional_str, optional_u64, required_str, SchemaProblem};
 use crate::storage::DelfStorageConnection;
+use crate::store;
+
+const SOFT_DELETE_KEYS: [&str; 2] = ["field", "grace_days"];
 
 /// The deletion types for a DelfObject
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum DeleteType {
     /// This object can be deleted by any incoming edge, but not directly
     ByAny,
@@ -20,28 +27,169 @@ pub enum DeleteType {
     ByXOnly(HashSet<String>),
     /// This object cannot be deleted
     NotDeleted,
+    /// This object is kept when reached by any incoming edge, but the listed fields are replaced so it no longer holds personal data.  The cascade carries on from it as if it were deleted.
+    Anonymize(Vec<Anonymization>),
+}
+
+/// What replaces the value of an anonymized field
+#[derive(Clone, Debug, PartialEq)]
+pub enum Replacement {
+    /// Set the field to null
+    Null,
+    /// Set the field to the given value
+    Constant(String),
+    /// Replace the field with a hash of its value, so equal values still match each other
+    Hash,
+}
+
+/// A field of an `anonymize` object and what replaces its value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anonymization {
+    pub field: String,
+    pub replacement: Replacement,
+}
+
+// Parse the `anonymize` map of field names to replacements
+fn parse_anonymize(yaml: &Yaml) -> Result<Vec<Anonymization>, Vec<SchemaProblem>> {
+    let hash = match yaml {
+        Yaml::Hash(hash) if hash.len() > 0 => hash,
+        _ => {
+            return Err(vec![SchemaProblem::new(
+                "anonymize",
+                String::from("`anonymize` objects must map the fields to replace to `null`, `hash`, or `{constant: value}` in `anonymize`"),
+            )])
+        }
+    };
+
+    let mut fields = Vec::new();
+    let mut problems = Vec::new();
+    for (field, replacement_yaml) in hash.iter() {
+        let field = match field.as_str() {
+            Some(field) => String::from(field),
+            None => {
+                problems.push(SchemaProblem::new(
+                    "anonymize",
+                    String::from("field names must be strings"),
+                ));
+                continue;
+            }
+        };
+        let replacement = match replacement_yaml {
+            Yaml::Null => Some(Replacement::Null),
+            Yaml::String(s) if s.to_lowercase() == "null" => Some(Replacement::Null),
+            Yaml::String(s) if s.to_lowercase() == "hash" => Some(Replacement::Hash),
+            Yaml::Hash(_) => match &replacement_yaml["constant"] {
+                Yaml::String(s) => Some(Replacement::Constant(s.clone())),
+                Yaml::Integer(i) => Some(Replacement::Constant(i.to_string())),
+                Yaml::Real(r) => Some(Replacement::Constant(r.clone())),
+                _ => None,
+            },
+            _ => None,
+        };
+        match replacement {
+            Some(replacement) => fields.push(Anonymization { field, replacement }),
+            None => problems.push(SchemaProblem::new(
+                &format!("anonymize.{}", field),
+                String::from("expected one of: null, hash, {constant: value}"),
+            )),
+        }
+    }
+
+    if problems.len() > 0 {
+        return Err(problems);
+    }
+    return Ok(fields);
 }
 
 impl DeleteType {
-    fn from(input: &str, x_yaml: Option<&Vec<Yaml>>) -> DeleteType {
+    /// Parse the deletion type of an object, the object's `x` yaml is required for `by_x_only` and its `anonymize` yaml for `anonymize`
+    fn parse(input: &str, obj: &Yaml) -> Result<DeleteType, Vec<SchemaProblem>> {
         match input.to_lowercase().as_str() {
-            "by_any" => DeleteType::ByAny,
-            "short_ttl" => DeleteType::ShortTTL,
-            "directly" => DeleteType::Directly,
-            "directly_only" => DeleteType::DirectlyOnly,
-            "by_x_only" => {
-                let mut x = HashSet::new();
-                for x_str in x_yaml.unwrap().iter() {
-                    x.insert(String::from(x_str.as_str().unwrap()));
+            "by_any" => Ok(DeleteType::ByAny),
+            "short_ttl" => Ok(DeleteType::ShortTTL),
+            "directly" => Ok(DeleteType::Directly),
+            "directly_only" => Ok(DeleteType::DirectlyOnly),
+            "by_x_only" => match obj["x"].as_vec() {
+                Some(x_vec) => {
+                    let mut x = HashSet::new();
+                    let mut problems = Vec::new();
+                    for (i, x_str) in x_vec.iter().enumerate() {
+                        match x_str.as_str() {
+                            Some(edge_name) => {
+                                x.insert(String::from(edge_name));
+                            }
+                            None => problems.push(SchemaProblem::new(
+                                &format!("x[{}]", i),
+                                String::from("`x` must be a list of edge names"),
+                            )),
+                        }
+                    }
+                    if problems.len() > 0 {
+                        Err(problems)
+                    } else {
+                        Ok(DeleteType::ByXOnly(x))
+                    }
                 }
-                DeleteType::ByXOnly(x)
-            }
-            "not_deleted" => DeleteType::NotDeleted,
-            _ => panic!("No Object type"),
+                None => Err(vec![SchemaProblem::new(
+                    "x",
+                    String::from("`by_x_only` objects must list the edges that can delete them in `x`"),
+                )]),
+            },
+            "not_deleted" => Ok(DeleteType::NotDeleted),
+            "anonymize" => Ok(DeleteType::Anonymize(parse_anonymize(&obj["anonymize"])?)),
+            _ => Err(vec![SchemaProblem::new(
+                "deletion",
+                format!(
+                    "unknown object deletion type `{}`, expected one of: by_any, short_ttl, directly, directly_only, by_x_only, not_deleted, anonymize",
+                    input
+                ),
+            )]),
+        }
+    }
+}
+
+impl fmt::Display for DeleteType {
+    /// Display the deletion type as it is written in the schema
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteType::ByAny => write!(f, "by_any"),
+            DeleteType::ShortTTL => write!(f, "short_ttl"),
+            DeleteType::Directly => write!(f, "directly"),
+            DeleteType::DirectlyOnly => write!(f, "directly_only"),
+            DeleteType::ByXOnly(_) => write!(f, "by_x_only"),
+            DeleteType::NotDeleted => write!(f, "not_deleted"),
+            DeleteType::Anonymize(_) => write!(f, "anonymize"),
         }
     }
 }
 
+/// Marks instances of an object deleted instead of removing them, from the object's `soft_delete` option
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoftDelete {
+    /// The `DATETIME` or `TIMESTAMP` field holding when an instance was marked deleted, which is null for instances that aren't
+    pub field: String,
+    /// How many days after being marked deleted an instance is purged (defaults to 30)
+    pub grace_days: u64,
+}
+
+impl SoftDelete {
+    fn from_yaml(yaml: &Yaml) -> Result<SoftDelete, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(yaml, &SOFT_DELETE_KEYS, &mut problems);
+        let field = required_str(yaml, "field", &mut problems);
+        let grace_days = optional_u64(yaml, "grace_days", &mut problems).unwrap_or(30);
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(SoftDelete {
+            field: field.unwrap(),
+            grace_days,
+        })
+    }
+}
+
 /// The DelfObject contains the information about the object as described in the schema
 #[derive(Clone, Debug)]
 pub struct DelfObject {
@@ -50,64 +198,160 @@ pub struct DelfObject {
     pub deletion: DeleteType,
     pub id_field: String,
     pub id_type: String,
+    /// Whether the `id_type` was given in the schema, rather than defaulting to `number`
+    pub id_type_declared: bool,
     pub time_field: Option<String>,
+    /// If set, instances are marked deleted and purged after a grace period
+    pub soft_delete: Option<SoftDelete>,
 }
 
-impl From<&Yaml> for DelfObject {
-    /// Construct a DelfObject from yaml.  The keys `name`, `storage`, `id_field`, and `deletion` are required.  `id_type can be specified as `string`, but otherwise defaults to `number`.  The the `deletion` is `short_ttl`, `time_field` is also required.
-    fn from(obj: &Yaml) -> DelfObject {
-        DelfObject {
-            name: String::from(obj["name"].as_str().unwrap()),
-            storage: String::from(obj["storage"].as_str().unwrap()),
-            id_field: String::from(obj["id"].as_str().unwrap()),
-            id_type: match obj["id_type"].as_str() {
-                Some(t) => t.to_string(),
-                None => "number".to_string(),
+// the keys which can be used in an object definition
+const OBJECT_KEYS: &[&str] = &[
+    "name",
+    "storage",
+    "deletion",
+    "id",
+    "id_type",
+    "x",
+    "anonymize",
+    "time_field",
+    "soft_delete",
+    "edge_types",
+];
+
+impl TryFrom<&Yaml> for DelfObject {
+    type Error = Vec<SchemaProblem>;
+
+    /// Construct a DelfObject from yaml.  The keys `name`, `storage`, `id`, and `deletion` are required.  `id_type` can be specified as `string`, but otherwise defaults to `number`.  If the `deletion` is `short_ttl`, `time_field` is also required, and if it is `anonymize`, `anonymize` is.  `soft_delete` optionally gives the field instances are marked deleted with.  All problems found in the yaml are returned together.
+    fn try_from(obj: &Yaml) -> Result<DelfObject, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(obj, OBJECT_KEYS, &mut problems);
+
+        let name = required_str(obj, "name", &mut problems);
+        let storage = required_str(obj, "storage", &mut problems);
+        let id_field = required_str(obj, "id", &mut problems);
+        let id_type_declared = !obj["id_type"].is_badvalue();
+        let id_type = match optional_str(obj, "id_type", &mut problems) {
+            Some(t) => match t.to_lowercase().as_str() {
+                "string" | "number" => Some(t),
+                _ => {
+                    problems.push(SchemaProblem::new(
+                        "id_type",
+                        format!("unknown id type `{}`, expected one of: string, number", t),
+                    ));
+                    None
+                }
             },
-            time_field: match obj["time_field"].as_str() {
-                Some(f) => Some(f.to_string()),
-                None => None,
+            None => Some("number".to_string()),
+        };
+        let time_field = optional_str(obj, "time_field", &mut problems);
+        let deletion = match required_str(obj, "deletion", &mut problems) {
+            Some(d) => match DeleteType::parse(&d, obj) {
+                Ok(deletion) => Some(deletion),
+                Err(mut e) => {
+                    problems.append(&mut e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let soft_delete = match &obj["soft_delete"] {
+            Yaml::BadValue => None,
+            yaml => match SoftDelete::from_yaml(yaml) {
+                Ok(soft_delete) => Some(soft_delete),
+                Err(e) => {
+                    problems.extend(e.into_iter().map(|p| p.within("soft_delete")));
+                    None
+                }
             },
-            deletion: DeleteType::from(obj["deletion"].as_str().unwrap(), obj["x"].as_vec()),
+        };
+
+        if let (Some(DeleteType::Anonymize(_)), Some(_)) = (&deletion, &soft_delete) {
+            problems.push(SchemaProblem::new(
+                "soft_delete",
+                String::from("`anonymize` objects keep their instances, so can't also `soft_delete` them"),
+            ));
         }
+
+        if deletion == Some(DeleteType::ShortTTL) && time_field.is_none() {
+            problems.push(SchemaProblem::new(
+                "time_field",
+                String::from("`short_ttl` objects must specify a `time_field`"),
+            ));
+        }
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(DelfObject {
+            name: name.unwrap(),
+            storage: storage.unwrap(),
+            id_field: id_field.unwrap(),
+            id_type: id_type.unwrap(),
+            id_type_declared,
+            time_field,
+            soft_delete,
+            deletion: deletion.unwrap(),
+        })
     }
 }
 
 impl DelfObject {
-    /// Delete an instance of this object given the id.
-    pub fn delete(
-        &self,
-        id: &String,
-        from_edge: Option<&DelfEdge>,
-        storages: &HashMap<String, Box<dyn DelfStorageConnection>>,
-    ) -> bool {
-        let mut to_delete = false;
+    /// Whether an instance of this object is deleted when it is reached through the given edge, or directly if there is no edge.
+    pub fn deletable_by(&self, from_edge: Option<&DelfEdge>) -> bool {
         match from_edge {
             Some(edge) => match &self.deletion {
-                DeleteType::ByAny | DeleteType::Directly | DeleteType::ShortTTL => {
-                    to_delete = true;
-                }
-                DeleteType::ByXOnly(x) => {
-                    if x.contains(&edge.name) {
-                        to_delete = true;
-                    }
-                }
-                _ => (),
+                DeleteType::ByAny
+                | DeleteType::Directly
+                | DeleteType::ShortTTL
+                | DeleteType::Anonymize(_) => true,
+                DeleteType::ByXOnly(x) => x.contains(&edge.name),
+                _ => false,
             },
             None => match &self.deletion {
-                DeleteType::DirectlyOnly | DeleteType::Directly | DeleteType::ShortTTL => {
-                    to_delete = true;
-                }
-                _ => (),
+                DeleteType::DirectlyOnly | DeleteType::Directly | DeleteType::ShortTTL => true,
+                _ => false,
             },
         }
+    }
 
-        if to_delete {
+    /// Delete an instance of this object given the id, or anonymize it if this is an `anonymize` object, or mark it deleted if it is soft deleted, unless it is in `held`, the (object name, id) of the instances under a legal hold.
+    pub fn delete(
+        &self,
+        id: &String,
+        from_edge: Option<&DelfEdge>,
+        storages: &HashMap<String, Box<dyn DelfStorageConnection>>,
+        held: &HashSet<(String, String)>,
+    ) -> bool {
+        if held.contains(&(self.name.clone(), id.clone())) {
+            println!("Object held, not deleted: {:#?} {}", self.name, id);
+            return false;
+        }
+        if self.deletable_by(from_edge) {
             let s = &*(storages.get(&self.storage).unwrap());
-            let deleted = s.delete_object(self, id);
-            if deleted {
-                println!("Object deleted: {:#?}", self.name);
-                return true;
+            match &self.deletion {
+                DeleteType::Anonymize(fields) => {
+                    if s.anonymize_object(self, id, fields) {
+                        println!("Object anonymized: {:#?}", self.name);
+                        return true;
+                    }
+                }
+                _ => match &self.soft_delete {
+                    Some(soft_delete) => {
+                        if s.soft_delete_object(self, id, &soft_delete.field, store::now()) {
+                            println!("Object soft deleted: {:#?}", self.name);
+                            return true;
+                        }
+                    }
+                    None => {
+                        if s.delete_object(self, id) {
+                            println!("Object deleted: {:#?}", self.name);
+                            return true;
+                        }
+                    }
+                },
             }
         }
 
@@ -119,8 +363,13 @@ impl DelfObject {
         &self,
         storages: &HashMap<String, Box<dyn DelfStorageConnection>>,
     ) -> Result<(), String> {
-        let s = &*(storages.get(&self.storage).unwrap());
-        return s.validate_object(self);
+        match storages.get(&self.storage) {
+            Some(s) => s.validate_object(self),
+            None => Err(format!(
+                "Object {} uses storage {}, which isn't in the config",
+                self.name, self.storage
+            )),
+        }
     }
 
     /// If the delete type is `short_ttl`, return ids of instances that are ready for deletion based on the elapsed time.
@@ -132,11 +381,212 @@ impl DelfObject {
             DeleteType::ShortTTL => match &self.time_field {
                 Some(field) => {
                     let s = &*(storages.get(&self.storage).unwrap());
-                    s.get_object_ids_by_time(&self.name, &field, &self.id_field, &self.id_type)
+                    s.get_object_ids_by_time(
+                        &self.name,
+                        &field,
+                        &self.id_field,
+                        &self.id_type,
+                        store::now(),
+                    )
                 }
                 None => panic!("short_ttl objects must have time_field specified"),
             },
             _ => Vec::new(),
         }
     }
+
+    /// The predicate matching the instances which aren't soft deleted, if this object is soft deleted
+    pub fn live_predicate(&self) -> Option<Predicate> {
+        return self
+            .soft_delete
+            .as_ref()
+            .map(|soft_delete| Predicate::is_null(&soft_delete.field));
+    }
+
+    /// If the object is soft deleted, return ids of instances marked deleted longer ago than the grace period.
+    pub fn check_purge(
+        &self,
+        storages: &HashMap<String, Box<dyn DelfStorageConnection>>,
+    ) -> Vec<String> {
+        match &self.soft_delete {
+            Some(soft_delete) => {
+                let s = &*(storages.get(&self.storage).unwrap());
+                let grace = soft_delete.grace_days * 24 * 60 * 60;
+                s.get_soft_deleted_ids(self, &soft_delete.field, store::now().saturating_sub(grace))
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store;
+
+    #[test]
+    fn anonymizes_instead_of_deleting() {
+        let path = std::env::temp_dir().join(format!("delf_anonymize_{}.jsonl", store::new_id()));
+        let config = format!("storages: []\nrestoration:\n  path: {}\n", path.to_str().unwrap());
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: anonymize
+  anonymize:
+    name: null
+    email: hash
+    note:
+      constant: removed
+  id: id
+  edge_types:
+    - name: review_comment
+      to:
+        object_type: comment
+        field: review_id
+      deletion: deep
+---
+object_type:
+  name: comment
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        memory.insert("contact", &[("id", "1")]);
+        let review = [("id", "10"), ("contact_id", "1"), ("name", "a"), ("email", "a@b.c"), ("note", "n")];
+        memory.insert("review", &review);
+        memory.insert("comment", &[("id", "20"), ("review_id", "10")]);
+
+        let deletion_id = graph
+            .delete_object(&String::from("contact"), &String::from("1"))
+            .unwrap()
+            .deletion_id
+            .unwrap();
+        assert_eq!(memory.column("contact", "id").len(), 0);
+        assert_eq!(memory.column("comment", "id").len(), 0);
+        assert_eq!(memory.column("review", "id"), vec!["10"]);
+        assert_eq!(memory.column("review", "note"), vec!["removed"]);
+        assert_ne!(memory.column("review", "email"), vec!["a@b.c"]);
+        assert!(!memory.tables.lock().unwrap()["review"][0].contains_key("name"));
+
+        graph.restore(&deletion_id).unwrap();
+        assert_eq!(memory.column("review", "name"), vec!["a"]);
+        assert_eq!(memory.column("review", "email"), vec!["a@b.c"]);
+        assert_eq!(memory.column("review", "note"), vec!["n"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn soft_deletes_then_purges() {
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_paper
+      to:
+        object_type: paper
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  soft_delete:
+    field: deleted_at
+    grace_days: 1
+  id: id
+  edge_types:
+    - name: paper_review
+      to:
+        object_type: review
+        field: paper_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("paper", &[("id", "10"), ("contact_id", "1")]);
+        memory.insert("paper", &[("id", "11"), ("contact_id", "1"), ("deleted_at", "100")]);
+        memory.insert("review", &[("id", "20"), ("paper_id", "10")]);
+        memory.insert("review", &[("id", "21"), ("paper_id", "11")]);
+
+        graph.delete_object(&String::from("contact"), &String::from("1")).unwrap();
+        assert_eq!(memory.column("paper", "id"), vec!["10", "11"]);
+        assert_eq!(memory.column("paper", "deleted_at")[1], "100");
+        assert!(memory.column("paper", "deleted_at")[0].parse::<u64>().unwrap() > 100);
+        // the cascade stops at soft deleted papers, so their reviews are kept until they're purged
+        assert_eq!(memory.column("review", "id"), vec!["20", "21"]);
+
+        // only the paper past its grace period is purged, carrying on the cascade to its reviews
+        assert_eq!(graph.purge_soft_deleted(), Ok(1));
+        assert_eq!(memory.column("paper", "id"), vec!["10"]);
+        assert_eq!(memory.column("review", "id"), vec!["20"]);
+    }
+
+    #[test]
+    fn ignores_soft_deleted_targets_of_mapping_edges() {
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_paper
+      to:
+        object_type: paper
+        field: contact_id
+        mapping_table: authorship
+      deletion: deep
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  soft_delete:
+    field: deleted_at
+    grace_days: 1
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph(schema);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("paper", &[("id", "10")]);
+        memory.insert("paper", &[("id", "11"), ("deleted_at", "100")]);
+        memory.insert("authorship", &[("contact_id", "1"), ("id", "10")]);
+        memory.insert("authorship", &[("contact_id", "1"), ("id", "11")]);
+
+        let edge = String::from("contact_paper");
+        let from_id = String::from("1");
+        assert!(graph.edge_exists(&edge, &from_id, &String::from("10")).unwrap());
+        assert!(!graph.edge_exists(&edge, &from_id, &String::from("11")).unwrap());
+
+        graph.delete_object(&String::from("contact"), &from_id).unwrap();
+        // the soft deleted paper keeps the time it was first deleted at
+        assert_eq!(memory.column("paper", "deleted_at")[1], "100");
+    }
 }
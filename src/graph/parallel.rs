@@ -0,0 +1,295 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use super::cascade::{Cascade, CascadeState};
+use super::checkpoint::Task;
+use super::{DelfGraph, Deletion};
+
+/// The tasks of a parallel cascade waiting to be run, and how many are running against each storage
+#[derive(Debug, Default)]
+struct PoolQueue {
+    // tasks waiting to be run, with the storage each runs against
+    pending: VecDeque<(Task, String)>,
+    // the number of tasks running
+    active: usize,
+    // the number of tasks running against each storage
+    active_by_storage: HashMap<String, usize>,
+    // whether the workers should stop
+    shutdown: bool,
+    // the error from the first task which failed
+    failed: Option<String>,
+}
+
+/// The queue a pool of worker threads takes cascade tasks from.  A task is only handed to a worker while fewer tasks than the storage's `concurrency` are running against it.
+#[derive(Debug)]
+pub(crate) struct WorkPool {
+    limits: HashMap<String, usize>,
+    queue: Mutex<PoolQueue>,
+    changed: Condvar,
+}
+
+impl WorkPool {
+    fn new(limits: HashMap<String, usize>) -> WorkPool {
+        WorkPool {
+            limits,
+            queue: Mutex::new(PoolQueue::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Add a task running against the storage to the queue
+    pub(crate) fn submit(&self, task: Task, storage: &String) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.pending.push_back((task, storage.clone()));
+        self.changed.notify_all();
+    }
+
+    // Wait for the first task whose storage has room for it, or for the pool to shut down
+    fn take(&self) -> Option<(Task, String)> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if queue.shutdown {
+                return None;
+            }
+
+            let limits = &self.limits;
+            let active_by_storage = &queue.active_by_storage;
+            let ready = queue.pending.iter().position(|(_, storage)| {
+                active_by_storage.get(storage).cloned().unwrap_or(0)
+                    < limits.get(storage).cloned().unwrap_or(1)
+            });
+
+            match ready {
+                Some(i) => {
+                    let (task, storage) = queue.pending.remove(i).unwrap();
+                    queue.active += 1;
+                    *queue.active_by_storage.entry(storage.clone()).or_insert(0) += 1;
+                    return Some((task, storage));
+                }
+                None => queue = self.changed.wait(queue).unwrap(),
+            }
+        }
+    }
+
+    // Record that a task against the storage has finished.  If it failed, the waiting tasks are dropped, as they're still in the checkpoint if one is kept.
+    fn done(&self, storage: &String, failure: Option<String>) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.active -= 1;
+        *queue.active_by_storage.get_mut(storage).unwrap() -= 1;
+        if let Some(e) = failure {
+            self.fail(&mut queue, e);
+        }
+        self.changed.notify_all();
+    }
+
+    fn fail(&self, queue: &mut PoolQueue, error: String) {
+        queue.pending.clear();
+        if queue.failed.is_none() {
+            queue.failed = Some(error);
+        }
+    }
+
+    // Wait until no tasks are waiting or running, returning the error if a task failed
+    fn wait_idle(&self) -> Option<String> {
+        let mut queue = self.queue.lock().unwrap();
+        while queue.active > 0 || queue.pending.len() > 0 {
+            queue = self.changed.wait(queue).unwrap();
+        }
+        return queue.failed.clone();
+    }
+
+    fn shutdown(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.shutdown = true;
+        self.changed.notify_all();
+    }
+}
+
+impl DelfGraph {
+    /// Whether any storage is configured with a `concurrency` above 1, so deletions are run in parallel
+    pub(crate) fn is_parallel(&self) -> bool {
+        return self.concurrency.values().any(|n| *n > 1);
+    }
+
+    /// Run a cascade from the task on the state with a pool of worker threads, each with its own connections to the storages.  Once an object instance is deleted, the edges from it are handed to the pool, and run with at most the storage's `concurrency` at once.
+    ///
+    /// A `refcount` check can find a reference which another thread is about to delete, so such checks are run again once the pool is idle, until a round deletes nothing more.  Instances in `child_first` storages are removed once the pool is done, the most recently reached first.  If any task fails, the cascade stops and the failure is returned as the error, leaving its checkpoint (if kept) to be resumed.  Returns what was deleted.
+    pub(crate) fn run_parallel(&self, task: Task, state: Arc<Mutex<CascadeState>>) -> Result<Deletion, String> {
+        let pool = Arc::new(WorkPool::new(self.concurrency.clone()));
+        let mut cascade = Cascade::with_state(self, false, state.clone(), Some(&*pool));
+        cascade.push_tasks(&[task.clone()])?;
+
+        let mut workers = Vec::new();
+        for _ in 0..self.concurrency.values().sum() {
+            let graph = self.reconnect();
+            let state = state.clone();
+            let pool = pool.clone();
+            workers.push(thread::spawn(move || {
+                while let Some((task, storage)) = pool.take() {
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        let mut cascade =
+                            Cascade::with_state(&graph, false, state.clone(), Some(&*pool));
+                        cascade.execute(&task);
+                        cascade.failure()?;
+                        cascade.pop_tasks(&[task.clone()]);
+                        return Ok(());
+                    }));
+                    let error = match result {
+                        Ok(r) => r.err(),
+                        Err(panic) => Some(panic_message(panic)),
+                    };
+                    pool.done(&storage, error);
+                }
+            }));
+        }
+
+        let root = catch_unwind(AssertUnwindSafe(|| {
+            cascade.execute(&task);
+            return cascade.failure();
+        }));
+        let root_error = match root {
+            Ok(r) => r.err(),
+            Err(panic) => Some(panic_message(panic)),
+        };
+        if let Some(e) = root_error {
+            let mut queue = pool.queue.lock().unwrap();
+            pool.fail(&mut queue, e);
+        }
+
+        let mut deleted = 0;
+        let failure = loop {
+            if let Some(e) = pool.wait_idle() {
+                break Some(e);
+            }
+
+            let (now_deleted, rechecks) = {
+                let mut state = state.lock().unwrap();
+                (state.deleted_count(), std::mem::take(&mut state.rechecks))
+            };
+            if rechecks.len() == 0 || now_deleted == deleted {
+                break None;
+            }
+            deleted = now_deleted;
+
+            if let Err(e) = cascade.push_tasks(&rechecks) {
+                break Some(e);
+            }
+            for recheck in rechecks.into_iter() {
+                if let Task::RefCount { edge, .. } = &recheck {
+                    let to_obj = self.get_object(&self.get_edge(edge).to.object_type);
+                    pool.submit(recheck.clone(), &to_obj.storage);
+                }
+            }
+        };
+
+        pool.shutdown();
+        for worker in workers.into_iter() {
+            worker.join().unwrap();
+        }
+        if let Some(e) = failure {
+            return Err(e);
+        }
+
+        // instances are reached before anything below them, so removing in reverse removes children before their parents
+        let deferred = std::mem::take(&mut state.lock().unwrap().deferred);
+        for remove in deferred.iter().rev() {
+            cascade.execute(remove);
+            cascade.pop_tasks(&[remove.clone()]);
+        }
+
+        cascade.pop_tasks(&[task]);
+        cascade.finish();
+        return Ok(cascade.deletion());
+    }
+}
+
+/// The message a panic was raised with
+pub(crate) fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast_ref::<String>() {
+        Some(msg) => msg.clone(),
+        None => match panic.downcast_ref::<&str>() {
+            Some(msg) => String::from(*msg),
+            None => String::from("deletion panicked"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::memory::MemoryConnection;
+    use crate::storage::DelfStorageConnection;
+
+    #[test]
+    fn parallel_cascade_keeps_refcounts() {
+        let url = format!("parallel_{}", crate::store::new_id());
+        let config = format!(
+            "storages:\n  - name: s\n    plugin: memory\n    url: {}\n    concurrency: 4\n",
+            url
+        );
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_lead
+      to:
+        object_type: paper
+        field: lead_id
+      deletion: refcount
+    - name: contact_shepherd
+      to:
+        object_type: paper
+        field: shepherd_id
+      deletion: refcount
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types:
+    - name: paper_review
+      to:
+        object_type: review
+        field: paper_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let graph = crate::read_yamls(schema, &config).unwrap();
+        let memory = MemoryConnection::connect(&url);
+        memory.insert("contact", &[("id", "1")]);
+        memory.insert("contact", &[("id", "2")]);
+        memory.insert("paper", &[("id", "10"), ("lead_id", "1"), ("shepherd_id", "1")]);
+        memory.insert("paper", &[("id", "11"), ("lead_id", "1"), ("shepherd_id", "2")]);
+        for id in 20..40 {
+            let paper_id = if id % 2 == 0 { "10" } else { "11" };
+            memory.insert(
+                "review",
+                &[("id", &id.to_string()), ("contact_id", "2"), ("paper_id", paper_id)],
+            );
+        }
+
+        assert!(graph.is_parallel());
+        graph.delete_object(&String::from("contact"), &String::from("1")).unwrap();
+
+        assert_eq!(memory.column("contact", "id"), vec!["2"]);
+        assert_eq!(memory.column("paper", "id"), vec!["11"]);
+        assert_eq!(memory.column("review", "id").len(), 10);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use ansi_term::Colour::{Cyan, Green, Yellow};
+use serde::Serialize;
+
+use super::hold::HeldInstance;
+
+/// An object instance which is deleted by a cascade, along with the edges deleted from it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PlannedObject {
+    /// The name of the object
+    pub object: String,
+    /// The id of the instance
+    pub id: String,
+    /// The name of the storage the instance is in
+    pub storage: String,
+    /// The edges from this instance which are deleted along with it
+    pub edges: Vec<PlannedEdge>,
+}
+
+/// The instances of an edge from one object instance which are deleted by a cascade
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PlannedEdge {
+    /// The name of the edge
+    pub edge: String,
+    /// The name of the storage the edge instances are in
+    pub storage: String,
+    /// The id of the object instance the edges start from
+    pub from_id: String,
+    /// The ids of the object instances the edges point to
+    pub to_ids: Vec<String>,
+    /// Object instances deleted through these edges
+    pub objects: Vec<PlannedObject>,
+    /// Inverse edges deleted along with these edges
+    pub inverses: Vec<PlannedEdge>,
+}
+
+/// A tree of the object and edge instances a deletion removes.  Returned from [plan_delete_object](../struct.DelfGraph.html#method.plan_delete_object) and [plan_delete_edge](../struct.DelfGraph.html#method.plan_delete_edge) without anything being deleted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DeletionPlan {
+    /// The object instances deleted directly, with everything they cascade to
+    pub objects: Vec<PlannedObject>,
+    /// The edge instances deleted directly, with everything they cascade to
+    pub edges: Vec<PlannedEdge>,
+    /// The instances skipped because they are under a legal hold
+    pub held: Vec<HeldInstance>,
+}
+
+impl DeletionPlan {
+    /// Whether nothing would be deleted
+    pub fn is_empty(&self) -> bool {
+        return self.objects.len() == 0 && self.edges.len() == 0;
+    }
+
+    /// The ids of all object instances in the plan, by storage and then by object name
+    pub fn instances_by_storage(&self) -> BTreeMap<String, BTreeMap<String, Vec<String>>> {
+        let mut instances = BTreeMap::new();
+        for obj in self.objects.iter() {
+            collect_object(obj, &mut instances);
+        }
+        for edge in self.edges.iter() {
+            collect_edge(edge, &mut instances);
+        }
+        return instances;
+    }
+
+    /// The number of object instances in the plan
+    pub fn object_count(&self) -> usize {
+        let mut count = 0;
+        for (_, objects) in self.instances_by_storage().iter() {
+            for (_, ids) in objects.iter() {
+                count += ids.len();
+            }
+        }
+        return count;
+    }
+
+    /// The plan serialized as json.
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string_pretty(self).unwrap();
+    }
+
+    /// Pretty print the plan as a tree, followed by a summary of the object instances per storage and the held instances skipped.
+    pub fn print(&self) {
+        println!("\u{1f4cb} {}", Cyan.bold().paint("Deletion plan"));
+        if self.is_empty() {
+            println!("  {}", Green.paint("Nothing would be deleted"));
+            self.print_held();
+            return;
+        }
+
+        for obj in self.objects.iter() {
+            print_object(obj, 1);
+        }
+        for edge in self.edges.iter() {
+            print_edge(edge, 1);
+        }
+
+        println!(
+            "\u{1f4e6} {}",
+            Cyan.bold().paint(format!("{} object instance(s) by storage", self.object_count()))
+        );
+        for (storage, objects) in self.instances_by_storage().iter() {
+            println!("  {}", storage);
+            for (object, ids) in objects.iter() {
+                println!("    {}: {}", object, ids.join(", "));
+            }
+        }
+        self.print_held();
+    }
+
+    // Print the held instances the deletion would skip
+    fn print_held(&self) {
+        if self.held.len() == 0 {
+            return;
+        }
+        println!(
+            "\u{1f6d1} {}",
+            Yellow.bold().paint(format!("{} held instance(s) skipped", self.held.len()))
+        );
+        for held in self.held.iter() {
+            println!("  {} {}", held.object, held.id);
+        }
+    }
+}
+
+fn collect_object(
+    obj: &PlannedObject,
+    instances: &mut BTreeMap<String, BTreeMap<String, Vec<String>>>,
+) {
+    instances
+        .entry(obj.storage.clone())
+        .or_insert_with(BTreeMap::new)
+        .entry(obj.object.clone())
+        .or_insert_with(Vec::new)
+        .push(obj.id.clone());
+    for edge in obj.edges.iter() {
+        collect_edge(edge, instances);
+    }
+}
+
+fn collect_edge(
+    edge: &PlannedEdge,
+    instances: &mut BTreeMap<String, BTreeMap<String, Vec<String>>>,
+) {
+    for obj in edge.objects.iter() {
+        collect_object(obj, instances);
+    }
+    for inverse in edge.inverses.iter() {
+        collect_edge(inverse, instances);
+    }
+}
+
+fn print_object(obj: &PlannedObject, depth: usize) {
+    println!(
+        "{}{} {} ({})",
+        "  ".repeat(depth),
+        obj.object,
+        obj.id,
+        obj.storage
+    );
+    for edge in obj.edges.iter() {
+        print_edge(edge, depth + 1);
+    }
+}
+
+fn print_edge(edge: &PlannedEdge, depth: usize) {
+    println!(
+        "{}-[{}]-> [{}] ({})",
+        "  ".repeat(depth),
+        edge.edge,
+        edge.to_ids.join(", "),
+        edge.storage
+    );
+    for obj in edge.objects.iter() {
+        print_object(obj, depth + 1);
+    }
+    for inverse in edge.inverses.iter() {
+        print_edge(inverse, depth + 1);
+    }
+}
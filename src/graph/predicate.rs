@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::edge::ListFormat;
+
+/// A value compared against a column in a predicate
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    /// A quoted string
+    Str(String),
+    /// A number, kept as written
+    Number(String),
+    /// `true` or `false`
+    Bool(bool),
+}
+
+/// How a column is compared with a value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A row predicate on an edge, given by its `where` key.  Predicates are a small expression language rather than raw SQL, so they can be checked against a table's columns and applied by every storage plugin:
+///
+/// ```text
+/// commentType != 'public' and (score >= 3 or flagged = true)
+/// deleted_at is null
+/// status in ('draft', 'hidden')
+/// not (status = 'published')
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    /// `column op value`
+    Compare {
+        column: String,
+        op: CompareOp,
+        value: Literal,
+    },
+    /// `column is null` or `column is not null`
+    IsNull { column: String, negated: bool },
+    /// `column in (values)` or `column not in (values)`
+    In {
+        column: String,
+        values: Vec<Literal>,
+        negated: bool,
+    },
+    /// The list of ids in a column holds the value.  Not part of the `where` syntax, this is used to find the rows of an edge stored as a list.
+    Contains {
+        column: String,
+        format: ListFormat,
+        value: Literal,
+    },
+    /// Every predicate holds
+    And(Vec<Predicate>),
+    /// Any predicate holds
+    Or(Vec<Predicate>),
+    /// The predicate doesn't hold
+    Not(Box<Predicate>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(Literal),
+    Op(CompareOp),
+    Open,
+    Close,
+    Comma,
+}
+
+impl Predicate {
+    /// Whether the column is null
+    pub fn is_null(column: &String) -> Predicate {
+        return Predicate::IsNull {
+            column: column.clone(),
+            negated: false,
+        };
+    }
+
+    /// `column = value`, for matching a single instance by its id
+    pub fn equals(column: &String, value: &String) -> Predicate {
+        return Predicate::Compare {
+            column: column.clone(),
+            op: CompareOp::Eq,
+            value: Literal::Str(value.clone()),
+        };
+    }
+
+    /// `column != value`
+    pub fn not_equals(column: &String, value: &String) -> Predicate {
+        return Predicate::Compare {
+            column: column.clone(),
+            op: CompareOp::Ne,
+            value: Literal::Str(value.clone()),
+        };
+    }
+
+    /// The list of ids in the column holds the id, of the given id type
+    pub fn contains(column: &String, format: ListFormat, id_type: &String, id: &String) -> Predicate {
+        return Predicate::Contains {
+            column: column.clone(),
+            format,
+            value: id_literal(id_type, id),
+        };
+    }
+
+    /// The column holds one of the ids, of the given id type
+    pub fn one_of(column: &String, id_type: &String, ids: &Vec<String>) -> Predicate {
+        return Predicate::In {
+            column: column.clone(),
+            values: ids.iter().map(|id| id_literal(id_type, id)).collect(),
+            negated: false,
+        };
+    }
+
+    /// Combine an optional predicate with another, so rows must match both
+    pub fn and(predicate: Option<&Predicate>, other: Predicate) -> Predicate {
+        match predicate {
+            Some(p) => Predicate::And(vec![p.clone(), other]),
+            None => other,
+        }
+    }
+
+    /// Parse a predicate, returning a description of the first problem found in it
+    pub fn parse(input: &str) -> Result<Predicate, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let predicate = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("unexpected {} in predicate", parser.describe()));
+        }
+        return Ok(predicate);
+    }
+
+    /// The names of the columns the predicate refers to
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns.sort();
+        columns.dedup();
+        return columns;
+    }
+
+    fn collect_columns(&self, columns: &mut Vec<String>) {
+        match self {
+            Predicate::Compare { column, .. }
+            | Predicate::IsNull { column, .. }
+            | Predicate::In { column, .. }
+            | Predicate::Contains { column, .. } => columns.push(column.clone()),
+            Predicate::And(predicates) | Predicate::Or(predicates) => {
+                for p in predicates.iter() {
+                    p.collect_columns(columns);
+                }
+            }
+            Predicate::Not(p) => p.collect_columns(columns),
+        }
+    }
+
+    /// The predicate as a SQL condition.  Column names are checked when parsed, and strings are escaped, so the condition is safe to add to a query.
+    pub fn to_sql(&self) -> String {
+        match self {
+            Predicate::Compare { column, op, value } => {
+                format!("{} {} {}", column, op, value.to_sql())
+            }
+            Predicate::IsNull { column, negated } => {
+                format!("{} IS {}NULL", column, if *negated { "NOT " } else { "" })
+            }
+            Predicate::In {
+                column,
+                values,
+                negated,
+            } => {
+                let values: Vec<String> = values.iter().map(|v| v.to_sql()).collect();
+                format!(
+                    "{} {}IN ({})",
+                    column,
+                    if *negated { "NOT " } else { "" },
+                    values.join(", ")
+                )
+            }
+            Predicate::Contains {
+                column,
+                format: ListFormat::JsonArray,
+                value,
+            } => {
+                // elements may be numbers or strings
+                let quoted = format!("JSON_CONTAINS({}, JSON_QUOTE({}))", column, Literal::Str(value.as_string()).to_sql());
+                match value {
+                    Literal::Number(n) => format!("JSON_CONTAINS({}, '{}') OR {}", column, n, quoted),
+                    _ => quoted,
+                }
+            }
+            Predicate::Contains {
+                column,
+                format: ListFormat::Csv,
+                value,
+            } => format!(
+                "FIND_IN_SET({}, REPLACE({}, ' ', '')) > 0",
+                Literal::Str(value.as_string()).to_sql(),
+                column
+            ),
+            Predicate::And(predicates) => join_sql(predicates, " AND "),
+            Predicate::Or(predicates) => join_sql(predicates, " OR "),
+            Predicate::Not(p) => format!("NOT ({})", p.to_sql()),
+        }
+    }
+
+    /// Whether a row, given as column names to values, matches the predicate.  A column missing from the row is null.  Values are compared as numbers if both are numbers, and as strings otherwise.
+    pub fn matches(&self, row: &HashMap<String, String>) -> bool {
+        match self {
+            Predicate::Compare { column, op, value } => match row.get(column) {
+                Some(v) => {
+                    let ordering = compare(v, value);
+                    match op {
+                        CompareOp::Eq => ordering == Some(std::cmp::Ordering::Equal),
+                        CompareOp::Ne => ordering != Some(std::cmp::Ordering::Equal),
+                        CompareOp::Lt => ordering == Some(std::cmp::Ordering::Less),
+                        CompareOp::Le => ordering.map_or(false, |o| o != std::cmp::Ordering::Greater),
+                        CompareOp::Gt => ordering == Some(std::cmp::Ordering::Greater),
+                        CompareOp::Ge => ordering.map_or(false, |o| o != std::cmp::Ordering::Less),
+                    }
+                }
+                None => false,
+            },
+            Predicate::IsNull { column, negated } => row.contains_key(column) == *negated,
+            Predicate::In {
+                column,
+                values,
+                negated,
+            } => match row.get(column) {
+                Some(v) => {
+                    values
+                        .iter()
+                        .any(|l| compare(v, l) == Some(std::cmp::Ordering::Equal))
+                        != *negated
+                }
+                None => false,
+            },
+            Predicate::Contains {
+                column,
+                format,
+                value,
+            } => match row.get(column) {
+                Some(v) => format.split(v).contains(&value.as_string()),
+                None => false,
+            },
+            Predicate::And(predicates) => predicates.iter().all(|p| p.matches(row)),
+            Predicate::Or(predicates) => predicates.iter().any(|p| p.matches(row)),
+            Predicate::Not(p) => !p.matches(row),
+        }
+    }
+}
+
+// An id as a literal.  Numbers are written into SQL as they are, so only ids which parse as numbers are kept as numbers.
+fn id_literal(id_type: &String, id: &String) -> Literal {
+    match id_type.to_lowercase().as_str() {
+        "number" if id.parse::<i64>().is_ok() => Literal::Number(id.clone()),
+        _ => Literal::Str(id.clone()),
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareOp::Eq => write!(f, "="),
+            CompareOp::Ne => write!(f, "<>"),
+            CompareOp::Lt => write!(f, "<"),
+            CompareOp::Le => write!(f, "<="),
+            CompareOp::Gt => write!(f, ">"),
+            CompareOp::Ge => write!(f, ">="),
+        }
+    }
+}
+
+impl Literal {
+    fn to_sql(&self) -> String {
+        match self {
+            Literal::Str(s) => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            Literal::Number(n) => n.clone(),
+            Literal::Bool(b) => String::from(if *b { "1" } else { "0" }),
+        }
+    }
+
+    fn as_string(&self) -> String {
+        match self {
+            Literal::Str(s) => s.clone(),
+            Literal::Number(n) => n.clone(),
+            Literal::Bool(b) => String::from(if *b { "1" } else { "0" }),
+        }
+    }
+}
+
+fn join_sql(predicates: &Vec<Predicate>, separator: &str) -> String {
+    let parts: Vec<String> = predicates
+        .iter()
+        .map(|p| format!("({})", p.to_sql()))
+        .collect();
+    return parts.join(separator);
+}
+
+// Compare a row's value with a literal, as numbers if both are numbers
+fn compare(value: &String, literal: &Literal) -> Option<std::cmp::Ordering> {
+    let literal = literal.as_string();
+    match (value.parse::<f64>(), literal.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => Some(value.as_str().cmp(literal.as_str())),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Op(CompareOp::Eq));
+                i += 1;
+            }
+            '!' | '<' | '>' => {
+                let next = chars.get(i + 1).cloned();
+                let (op, len) = match (c, next) {
+                    ('!', Some('=')) => (CompareOp::Ne, 2),
+                    ('<', Some('>')) => (CompareOp::Ne, 2),
+                    ('<', Some('=')) => (CompareOp::Le, 2),
+                    ('>', Some('=')) => (CompareOp::Ge, 2),
+                    ('<', _) => (CompareOp::Lt, 1),
+                    ('>', _) => (CompareOp::Gt, 1),
+                    _ => return Err(String::from("unexpected `!` in predicate, expected `!=`")),
+                };
+                tokens.push(Token::Op(op));
+                i += len;
+            }
+            '\'' | '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some(q) if *q == c => break,
+                        Some(ch) => value.push(*ch),
+                        None => return Err(String::from("unterminated string in predicate")),
+                    }
+                    i += 1;
+                }
+                tokens.push(Token::Literal(Literal::Str(value)));
+                i += 1;
+            }
+            _ if c.is_ascii_digit() || c == '-' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                if number.parse::<f64>().is_err() {
+                    return Err(format!("`{}` in predicate isn't a number", number));
+                }
+                tokens.push(Token::Literal(Literal::Number(number)));
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                match word.to_lowercase().as_str() {
+                    "true" => tokens.push(Token::Literal(Literal::Bool(true))),
+                    "false" => tokens.push(Token::Literal(Literal::Bool(false))),
+                    _ => tokens.push(Token::Ident(word)),
+                }
+            }
+            _ => return Err(format!("unexpected `{}` in predicate", c)),
+        }
+    }
+    return Ok(tokens);
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.pos);
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(Token::Ident(word)) => format!("`{}`", word),
+            Some(Token::Literal(l)) => format!("`{}`", l.as_string()),
+            Some(Token::Op(op)) => format!("`{}`", op),
+            Some(Token::Open) => String::from("`(`"),
+            Some(Token::Close) => String::from("`)`"),
+            Some(Token::Comma) => String::from("`,`"),
+            None => String::from("end"),
+        }
+    }
+
+    // Consume the next token if it's the given keyword
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<(), String> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            return Ok(());
+        }
+        return Err(format!("expected {} in predicate, found {}", expected, self.describe()));
+    }
+
+    fn or(&mut self) -> Result<Predicate, String> {
+        let mut predicates = vec![self.and()?];
+        while self.keyword("or") {
+            predicates.push(self.and()?);
+        }
+        if predicates.len() == 1 {
+            return Ok(predicates.pop().unwrap());
+        }
+        return Ok(Predicate::Or(predicates));
+    }
+
+    fn and(&mut self) -> Result<Predicate, String> {
+        let mut predicates = vec![self.not()?];
+        while self.keyword("and") {
+            predicates.push(self.not()?);
+        }
+        if predicates.len() == 1 {
+            return Ok(predicates.pop().unwrap());
+        }
+        return Ok(Predicate::And(predicates));
+    }
+
+    fn not(&mut self) -> Result<Predicate, String> {
+        if self.keyword("not") {
+            return Ok(Predicate::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let predicate = self.or()?;
+            self.expect(Token::Close, "`)`")?;
+            return Ok(predicate);
+        }
+        return self.condition();
+    }
+
+    fn condition(&mut self) -> Result<Predicate, String> {
+        let column = match self.peek() {
+            Some(Token::Ident(word)) if !is_keyword(word) => word.clone(),
+            _ => return Err(format!("expected a column in predicate, found {}", self.describe())),
+        };
+        self.pos += 1;
+
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("null") {
+                return Err(format!("expected `null` in predicate, found {}", self.describe()));
+            }
+            return Ok(Predicate::IsNull { column, negated });
+        }
+
+        let negated = self.keyword("not");
+        if self.keyword("in") {
+            self.expect(Token::Open, "`(`")?;
+            let mut values = vec![self.literal()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.literal()?);
+            }
+            self.expect(Token::Close, "`)`")?;
+            return Ok(Predicate::In {
+                column,
+                values,
+                negated,
+            });
+        }
+        if negated {
+            return Err(format!("expected `in` in predicate, found {}", self.describe()));
+        }
+
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => {
+                return Err(format!(
+                    "expected a comparison (=, !=, <, <=, >, >=, is, in) in predicate, found {}",
+                    self.describe()
+                ))
+            }
+        };
+        self.pos += 1;
+        let value = self.literal()?;
+        return Ok(Predicate::Compare { column, op, value });
+    }
+
+    fn literal(&mut self) -> Result<Literal, String> {
+        match self.peek() {
+            Some(Token::Literal(l)) => {
+                let l = l.clone();
+                self.pos += 1;
+                Ok(l)
+            }
+            _ => Err(format!("expected a value in predicate, found {}", self.describe())),
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    return ["and", "or", "not", "is", "in", "null"]
+        .iter()
+        .any(|k| word.eq_ignore_ascii_case(k));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_matches() {
+        let predicate =
+            Predicate::parse("commentType != 'public' and (score >= 3 or status in ('a', 'b'))")
+                .unwrap();
+        assert_eq!(predicate.columns(), vec!["commentType", "score", "status"]);
+        assert_eq!(
+            predicate.to_sql(),
+            "(commentType <> 'public') AND ((score >= 3) OR (status IN ('a', 'b')))"
+        );
+
+        let row = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (String::from(*k), String::from(*v)))
+                .collect()
+        };
+        assert!(predicate.matches(&row(&[("commentType", "private"), ("score", "10")])));
+        assert!(!predicate.matches(&row(&[("commentType", "public"), ("score", "10")])));
+        assert!(!predicate.matches(&row(&[("commentType", "private"), ("score", "2")])));
+
+        assert!(Predicate::parse("name = 'x'; DROP TABLE users").is_err());
+        assert!(Predicate::parse("score >").is_err());
+        assert!(Predicate::parse("1 = 1").is_err());
+    }
+}
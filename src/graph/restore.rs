@@ -0,0 +1,295 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use yaml_rust::Yaml;
+
+use super::schema::{check_keys, optional_u64, required_str, SchemaProblem};
+use super::DelfGraph;
+use crate::storage::Row;
+use crate::store;
+
+const RESTORATION_KEYS: [&str; 2] = ["path", "retention_days"];
+
+/// The rows changed by one storage call during a deletion
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    /// Rows of an object's table which were deleted
+    Object { rows: Vec<Row> },
+    /// Rows of a mapping table which were deleted
+    Mapping { rows: Vec<Row> },
+    /// Rows whose edge field was cleared, as they were before clearing
+    Field {
+        id_field: String,
+        field: String,
+        rows: Vec<Row>,
+    },
+}
+
+impl Change {
+    // Rows are restored parents first: objects, then the mapping rows and fields which refer to them
+    fn restore_order(&self) -> usize {
+        match self {
+            Change::Object { .. } => 0,
+            Change::Mapping { .. } => 1,
+            Change::Field { .. } => 2,
+        }
+    }
+}
+
+/// A snapshot of the rows changed by one storage call during a deletion
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RestorationRecord {
+    /// The id of the deletion the change was part of
+    pub deletion_id: String,
+    /// The order of the change within the deletion
+    pub sequence: usize,
+    /// When the change was made, in seconds since the unix epoch
+    pub timestamp: u64,
+    /// The name of the storage the rows are in
+    pub storage: String,
+    /// The name of the table the rows are in
+    pub table: String,
+    /// The rows changed
+    pub change: Change,
+}
+
+/// A log of the rows deleted by a DelfGraph, kept as a json lines file so deletions can be restored within the retention window.  Configured by the `restoration` section of the config.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestorationLog {
+    /// The path of the json lines file the log is kept in
+    pub path: String,
+    /// How long deletions can be restored for, in days
+    pub retention_days: u64,
+}
+
+impl RestorationLog {
+    /// Read the `restoration` section of a config
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Result<RestorationLog, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(yaml, &RESTORATION_KEYS, &mut problems);
+        let path = required_str(yaml, "path", &mut problems);
+        let retention_days = optional_u64(yaml, "retention_days", &mut problems);
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(RestorationLog {
+            path: path.unwrap(),
+            retention_days: retention_days.unwrap_or(30),
+        })
+    }
+
+    /// Add a record to the end of the log
+    pub(crate) fn append(&self, record: &RestorationRecord) -> Result<(), String> {
+        return store::locked(&self.path, || {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .map_err(|e| format!("couldn't open {}: {}", self.path, e))?;
+            writeln!(file, "{}", serde_json::to_string(record).unwrap())
+                .map_err(|e| format!("couldn't write {}: {}", self.path, e))
+        });
+    }
+
+    /// All the records in the log, oldest first
+    pub fn records(&self) -> Result<Vec<RestorationRecord>, String> {
+        return store::locked(&self.path, || self.read());
+    }
+
+    // Read the records, without taking the lock on the log
+    fn read(&self) -> Result<Vec<RestorationRecord>, String> {
+        if !Path::new(&self.path).exists() {
+            return Ok(Vec::new());
+        }
+        let file = File::open(&self.path).map_err(|e| format!("couldn't open {}: {}", self.path, e))?;
+
+        let mut records = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("couldn't read {}: {}", self.path, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => return Err(format!("{} line {}: {}", self.path, i + 1, e)),
+            }
+        }
+        return Ok(records);
+    }
+
+    /// Remove the records which are older than the retention window
+    pub(crate) fn prune(&self) -> Result<(), String> {
+        return store::locked(&self.path, || {
+            let records = self.read()?;
+            let kept: Vec<RestorationRecord> = records
+                .iter()
+                .filter(|r| !self.is_expired(r))
+                .cloned()
+                .collect();
+            if kept.len() != records.len() {
+                self.write(&kept)?;
+            }
+            return Ok(());
+        });
+    }
+
+    /// Whether the record is older than the retention window
+    pub fn is_expired(&self, record: &RestorationRecord) -> bool {
+        return store::now().saturating_sub(record.timestamp) > self.retention_days * 24 * 60 * 60;
+    }
+
+    // Replace the records, without taking the lock on the log
+    fn write(&self, records: &Vec<RestorationRecord>) -> Result<(), String> {
+        let mut contents = String::new();
+        for record in records.iter() {
+            contents.push_str(&serde_json::to_string(record).unwrap());
+            contents.push('\n');
+        }
+        return store::replace(&self.path, contents);
+    }
+}
+
+impl DelfGraph {
+    /// Restore the rows removed by a deletion, given the deletion id returned when it was made.  Object rows are re-inserted first, then mapping table rows, then cleared edge fields are set back, so rows are restored before anything which refers to them.  Returns the number of rows restored, and removes the deletion from the log.
+    pub fn restore(&self, deletion_id: &String) -> Result<usize, String> {
+        let log = match &self.restoration {
+            Some(log) => log,
+            None => return Err(String::from("No restoration log is configured")),
+        };
+
+        // the log stays locked, so records appended by other deletions meanwhile aren't lost when it's rewritten
+        return store::locked(&log.path, || self.restore_from(log, deletion_id));
+    }
+
+    // Restore the rows of the deletion and remove it from the log, which the caller holds the lock on
+    fn restore_from(&self, log: &RestorationLog, deletion_id: &String) -> Result<usize, String> {
+        let (mut records, rest): (Vec<RestorationRecord>, Vec<RestorationRecord>) = log
+            .read()?
+            .into_iter()
+            .partition(|r| r.deletion_id == *deletion_id);
+
+        if records.len() == 0 {
+            return Err(format!("No deletion {} in the restoration log", deletion_id));
+        }
+        if records.iter().any(|r| log.is_expired(r)) {
+            return Err(format!(
+                "Deletion {} is older than the retention window of {} days",
+                deletion_id, log.retention_days
+            ));
+        }
+        for record in records.iter() {
+            if !self.storages.contains_key(&record.storage) {
+                return Err(format!(
+                    "Deletion {} uses storage {}, which isn't in the config",
+                    deletion_id, record.storage
+                ));
+            }
+        }
+
+        // child-first storages removed objects children first, so they're restored in reverse
+        records.sort_by_key(|r| {
+            let sequence = r.sequence as i64;
+            match r.change {
+                Change::Object { .. } if self.child_first.contains(&r.storage) => {
+                    (r.change.restore_order(), -sequence)
+                }
+                _ => (r.change.restore_order(), sequence),
+            }
+        });
+
+        let mut restored = 0;
+        for record in records.iter() {
+            let s = self.storages.get(&record.storage).unwrap();
+            restored += match &record.change {
+                Change::Object { rows } | Change::Mapping { rows } => {
+                    s.insert_rows(&record.table, rows)
+                }
+                Change::Field {
+                    id_field,
+                    field,
+                    rows,
+                } => s.update_field(&record.table, id_field, field, rows),
+            };
+        }
+
+        log.write(&rest)?;
+        return Ok(restored);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_deletion() {
+        let path = std::env::temp_dir().join(format!(
+            "delf_restore_{}.jsonl",
+            store::new_id()
+        ));
+        let config = format!(
+            "storages: []\nrestoration:\n  path: {}\n",
+            path.to_str().unwrap()
+        );
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_paper
+      to:
+        object_type: paper
+        field: contact_id
+        mapping_table: contact_paper
+      deletion: deep
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: shallow
+---
+object_type:
+  name: paper
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+---
+object_type:
+  name: review
+  storage: s
+  deletion: not_deleted
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        memory.insert("contact", &[("id", "1"), ("name", "a")]);
+        memory.insert("paper", &[("id", "10")]);
+        memory.insert("contact_paper", &[("contact_id", "1"), ("id", "10")]);
+        memory.insert("review", &[("id", "20"), ("contact_id", "1")]);
+
+        let deletion_id = graph
+            .delete_object(&String::from("contact"), &String::from("1"))
+            .unwrap()
+            .deletion_id
+            .unwrap();
+        assert_eq!(memory.column("contact", "id").len(), 0);
+        assert_eq!(memory.column("review", "contact_id"), vec!["0"]);
+
+        assert_eq!(graph.restore(&deletion_id), Ok(4));
+        assert_eq!(memory.column("contact", "name"), vec!["a"]);
+        assert_eq!(memory.column("paper", "id"), vec!["10"]);
+        assert_eq!(memory.column("contact_paper", "id"), vec!["10"]);
+        assert_eq!(memory.column("review", "contact_id"), vec!["1"]);
+        assert!(graph.restore(&deletion_id).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use serde::{Deserialize, Serialize};
+use yaml_rust::Yaml;
+
+use super::parallel::panic_message;
+use super::schema::{check_keys, required_str, SchemaProblem};
+use super::DelfGraph;
+use crate::store;
+
+const SCHEDULES_KEYS: [&str; 1] = ["path"];
+
+/// Where scheduled deletions are kept, from the `schedules` section of the config
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleConfig {
+    /// The json file the scheduled deletions are kept in
+    pub path: String,
+}
+
+impl ScheduleConfig {
+    /// Read the `schedules` section of a config
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Result<ScheduleConfig, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(yaml, &SCHEDULES_KEYS, &mut problems);
+        let path = required_str(yaml, "path", &mut problems);
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(ScheduleConfig {
+            path: path.unwrap(),
+        })
+    }
+
+    /// All the deletions scheduled and not yet run
+    pub fn scheduled(&self) -> Result<Vec<ScheduledDeletion>, String> {
+        return store::load(&self.path);
+    }
+}
+
+/// The deletion of an object instance, to be run once it is due unless it is cancelled first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledDeletion {
+    /// The name of the object
+    pub object_type: String,
+    /// The id of the instance
+    pub id: String,
+    /// When the deletion was scheduled, in seconds since the unix epoch
+    pub requested: u64,
+    /// When the deletion is due, in seconds since the unix epoch
+    pub due: u64,
+    /// The error from running the deletion, if it failed.  Failed deletions aren't run again until they are rescheduled.
+    pub error: Option<String>,
+}
+
+impl DelfGraph {
+    fn schedule_config(&self) -> Result<&ScheduleConfig, String> {
+        match &self.schedules {
+            Some(config) => Ok(config),
+            None => Err(String::from("No schedule store is configured")),
+        }
+    }
+
+    /// All the deletions scheduled and not yet run
+    pub fn scheduled_deletions(&self) -> Result<Vec<ScheduledDeletion>, String> {
+        return self.schedule_config()?.scheduled();
+    }
+
+    /// Schedule the deletion of an object instance for the given time, in seconds since the unix epoch.  Scheduling an instance which is already scheduled moves its deletion to the new time.
+    pub fn schedule_deletion(
+        &self,
+        object_type: &String,
+        id: &String,
+        due: u64,
+    ) -> Result<ScheduledDeletion, String> {
+        let config = self.schedule_config()?;
+        if !self.nodes.contains_key(object_type) {
+            return Err(format!("Unknown object type: {}", object_type));
+        }
+
+        let scheduled = ScheduledDeletion {
+            object_type: object_type.clone(),
+            id: id.clone(),
+            requested: store::now(),
+            due,
+            error: None,
+        };
+        return store::locked(&config.path, || {
+            let mut schedules = config.scheduled()?;
+            schedules.retain(|s| !(s.object_type == *object_type && s.id == *id));
+            schedules.push(scheduled.clone());
+            store::save(&config.path, &schedules)?;
+            return Ok(scheduled);
+        });
+    }
+
+    /// Cancel the scheduled deletion of an object instance, returning the deletion cancelled
+    pub fn cancel_deletion(&self, object_type: &String, id: &String) -> Result<ScheduledDeletion, String> {
+        let config = self.schedule_config()?;
+        return store::locked(&config.path, || {
+            let (cancelled, kept): (Vec<ScheduledDeletion>, Vec<ScheduledDeletion>) = config
+                .scheduled()?
+                .into_iter()
+                .partition(|s| s.object_type == *object_type && s.id == *id);
+
+            match cancelled.into_iter().next() {
+                Some(scheduled) => {
+                    store::save(&config.path, &kept)?;
+                    Ok(scheduled)
+                }
+                None => Err(format!("No deletion of {} {} is scheduled", object_type, id)),
+            }
+        });
+    }
+
+    /// Run the scheduled deletions which are due.  Each is removed once it has run, or kept with its error if it failed, including a storage error raised as a panic.  Returns the number run.  Does nothing if no schedule store is configured.
+    pub fn run_due_deletions(&self) -> Result<usize, String> {
+        let config = match &self.schedules {
+            Some(config) => config,
+            None => return Ok(0),
+        };
+
+        let now = store::now();
+        let due: Vec<ScheduledDeletion> = config
+            .scheduled()?
+            .into_iter()
+            .filter(|s| s.due <= now && s.error.is_none())
+            .collect();
+
+        let mut run = 0;
+        for scheduled in due.iter() {
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                self.delete_object(&scheduled.object_type, &scheduled.id)
+            }))
+            .unwrap_or_else(|panic| Err(panic_message(panic)));
+
+            // the store is read again, as deletions may have been scheduled or cancelled while this one ran
+            store::locked(&config.path, || {
+                let mut schedules = config.scheduled()?;
+                match result {
+                    Ok(_) => {
+                        run += 1;
+                        schedules.retain(|s| *s != *scheduled);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        if let Some(s) = schedules.iter_mut().find(|s| **s == *scheduled) {
+                            s.error = Some(e);
+                        }
+                    }
+                }
+                store::save(&config.path, &schedules)
+            })?;
+        }
+        return Ok(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_due_deletions() {
+        let path = std::env::temp_dir().join(format!("delf_schedules_{}.json", store::new_id()));
+        let config = format!("storages: []\nschedules:\n  path: {}\n", path.to_str().unwrap());
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        for id in ["1", "2", "3"].iter() {
+            memory.insert("contact", &[("id", id)]);
+        }
+
+        let contact = String::from("contact");
+        let now = store::now();
+        graph.schedule_deletion(&contact, &String::from("1"), now - 1).unwrap();
+        graph.schedule_deletion(&contact, &String::from("2"), now + 1000).unwrap();
+        graph.schedule_deletion(&contact, &String::from("3"), now - 1).unwrap();
+        graph.cancel_deletion(&contact, &String::from("3")).unwrap();
+        assert!(graph.schedule_deletion(&String::from("paper"), &String::from("1"), now).is_err());
+
+        assert_eq!(graph.run_due_deletions(), Ok(1));
+        assert_eq!(memory.column("contact", "id"), vec!["2", "3"]);
+        let scheduled = graph.scheduled_deletions().unwrap();
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].id, "2");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// A single problem found while loading a DelF schema
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaProblem {
+    /// The index of the yaml document (`---` separated) the problem was found in
+    pub document: usize,
+    /// The path of keys to the problem within the document, e.g. `object_type.edge_types[0].deletion`
+    pub path: String,
+    /// The line in the schema source the problem was found on (starting at 1)
+    pub line: usize,
+    /// The column in the schema source the problem was found on (starting at 1)
+    pub column: usize,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl SchemaProblem {
+    /// Create a problem at a key path which hasn't been located in the source yet
+    pub(crate) fn new(path: &str, message: String) -> SchemaProblem {
+        SchemaProblem {
+            document: 0,
+            path: String::from(path),
+            line: 0,
+            column: 0,
+            message,
+        }
+    }
+
+    /// Move the problem's key path underneath the given parent path
+    pub(crate) fn within(mut self, parent: &str) -> SchemaProblem {
+        self.path = join_path(parent, &self.path);
+        self
+    }
+}
+
+impl fmt::Display for SchemaProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "document {}, line {}, column {} ({}): {}",
+            self.document, self.line, self.column, self.path, self.message
+        )
+    }
+}
+
+/// The error returned when a DelF schema can't be loaded.  Contains every problem found in the schema so they can all be fixed at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaError {
+    pub problems: Vec<SchemaProblem>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} problem(s) found in schema:", self.problems.len())?;
+        for problem in self.problems.iter() {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SchemaError {}
+
+/// The source locations of the keys and values in a schema, by document index and key path.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaMarkers {
+    markers: HashMap<(usize, String), Marker>,
+}
+
+impl SchemaMarkers {
+    /// Set the document, line, and column of a problem from its key path.  If the path doesn't exist in the source (e.g. a missing key), the closest parent is used.
+    pub(crate) fn locate(&self, document: usize, mut problem: SchemaProblem) -> SchemaProblem {
+        problem.document = document;
+        let mut path = problem.path.as_str();
+        loop {
+            if let Some(marker) = self.markers.get(&(document, String::from(path))) {
+                problem.line = marker.line();
+                problem.column = marker.col() + 1;
+                break;
+            }
+            if path.is_empty() {
+                break;
+            }
+            path = parent_path(path);
+        }
+        problem
+    }
+}
+
+/// Load the yaml documents of a schema along with the source location of each key.
+pub fn load(source: &str) -> Result<(Vec<Yaml>, SchemaMarkers), SchemaError> {
+    let mut recorder = MarkerRecorder {
+        document: None,
+        stack: Vec::new(),
+        markers: SchemaMarkers::default(),
+    };
+
+    let mut parser = Parser::new(source.chars());
+    match parser.load(&mut recorder, true) {
+        Ok(_) => (),
+        Err(e) => {
+            return Err(SchemaError {
+                problems: vec![SchemaProblem {
+                    document: recorder.document.unwrap_or(0),
+                    path: String::new(),
+                    line: e.marker().line(),
+                    column: e.marker().col() + 1,
+                    message: e.to_string(),
+                }],
+            })
+        }
+    }
+
+    // the source already parsed successfully above
+    let docs = YamlLoader::load_from_str(source).unwrap();
+    return Ok((docs, recorder.markers));
+}
+
+/// Get a required string value from a yaml hash, recording a problem if it is missing or not a string.
+pub(crate) fn required_str(
+    yaml: &Yaml,
+    key: &str,
+    problems: &mut Vec<SchemaProblem>,
+) -> Option<String> {
+    match &yaml[key] {
+        Yaml::BadValue => {
+            problems.push(SchemaProblem::new(
+                key,
+                format!("missing required key `{}`", key),
+            ));
+            None
+        }
+        _ => optional_str(yaml, key, problems),
+    }
+}
+
+/// Get an optional string value from a yaml hash, recording a problem if it is present but not a string.
+pub(crate) fn optional_str(
+    yaml: &Yaml,
+    key: &str,
+    problems: &mut Vec<SchemaProblem>,
+) -> Option<String> {
+    match &yaml[key] {
+        Yaml::String(s) => Some(s.clone()),
+        Yaml::BadValue => None,
+        _ => {
+            problems.push(SchemaProblem::new(
+                key,
+                format!("`{}` must be a string", key),
+            ));
+            None
+        }
+    }
+}
+
+/// Get an optional non-negative integer value from a yaml hash, recording a problem if it is present but not a non-negative integer.
+pub(crate) fn optional_u64(
+    yaml: &Yaml,
+    key: &str,
+    problems: &mut Vec<SchemaProblem>,
+) -> Option<u64> {
+    match &yaml[key] {
+        Yaml::Integer(i) if *i >= 0 => Some(*i as u64),
+        Yaml::BadValue => None,
+        _ => {
+            problems.push(SchemaProblem::new(
+                key,
+                format!("`{}` must be a non-negative integer", key),
+            ));
+            None
+        }
+    }
+}
+
+/// Record a problem for every key in a yaml hash which isn't in the list of known keys.  If the yaml isn't a hash, a problem is recorded for that instead.
+pub(crate) fn check_keys(yaml: &Yaml, known: &[&str], problems: &mut Vec<SchemaProblem>) {
+    match yaml.as_hash() {
+        Some(hash) => {
+            for (key, _) in hash.iter() {
+                match key.as_str() {
+                    Some(k) if known.contains(&k) => (),
+                    Some(k) => problems.push(SchemaProblem::new(
+                        k,
+                        format!("unknown key `{}`, expected one of: {}", k, known.join(", ")),
+                    )),
+                    None => problems.push(SchemaProblem::new(
+                        "",
+                        format!("keys must be strings, found {:?}", key),
+                    )),
+                }
+            }
+        }
+        None => problems.push(SchemaProblem::new(
+            "",
+            format!("expected a mapping with the keys: {}", known.join(", ")),
+        )),
+    }
+}
+
+fn join_path(parent: &str, child: &str) -> String {
+    if parent.is_empty() {
+        String::from(child)
+    } else if child.is_empty() {
+        String::from(parent)
+    } else if child.starts_with('[') {
+        format!("{}{}", parent, child)
+    } else {
+        format!("{}.{}", parent, child)
+    }
+}
+
+fn parent_path(path: &str) -> &str {
+    match path.rfind(|c| c == '.' || c == '[') {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+// the containers the recorder is currently inside of
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+// Receives parser events and records the marker of each key path in each document
+struct MarkerRecorder {
+    document: Option<usize>,
+    stack: Vec<Frame>,
+    markers: SchemaMarkers,
+}
+
+impl MarkerRecorder {
+    // keys are recorded before their values, so a value keeps pointing at its key
+    fn record(&mut self, path: String, mark: Marker) {
+        let document = self.document.unwrap_or(0);
+        self.markers.markers.entry((document, path)).or_insert(mark);
+    }
+
+    // Work out the path of a new node.  Returns None if the node is a key in a mapping.
+    fn node_path(&mut self, mark: Marker, scalar: Option<&String>) -> Option<String> {
+        let (path, is_key) = match self.stack.last_mut() {
+            None => (String::new(), false),
+            Some(Frame::Sequence { path, index }) => {
+                let p = format!("{}[{}]", path, index);
+                *index += 1;
+                (p, false)
+            }
+            Some(Frame::Mapping { path, key }) => match key {
+                None => {
+                    let k = match scalar {
+                        Some(s) => s.clone(),
+                        None => String::from("?"),
+                    };
+                    let p = join_path(path, &k);
+                    *key = Some(k);
+                    (p, true)
+                }
+                Some(k) => (join_path(path, k), false),
+            },
+        };
+
+        self.record(path.clone(), mark);
+        if is_key {
+            None
+        } else {
+            Some(path)
+        }
+    }
+
+    // After a value is finished, the parent mapping expects a new key
+    fn end_value(&mut self) {
+        if let Some(Frame::Mapping { key, .. }) = self.stack.last_mut() {
+            *key = None;
+        }
+    }
+}
+
+impl MarkedEventReceiver for MarkerRecorder {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::DocumentStart => {
+                self.document = Some(self.document.map_or(0, |d| d + 1));
+                self.stack.clear();
+            }
+            Event::Scalar(v, ..) => {
+                if self.node_path(mark, Some(&v)).is_some() {
+                    self.end_value();
+                }
+            }
+            Event::Alias(_) => {
+                if self.node_path(mark, None).is_some() {
+                    self.end_value();
+                }
+            }
+            Event::MappingStart(_) => {
+                let path = self.node_path(mark, None).unwrap_or_default();
+                self.stack.push(Frame::Mapping { path, key: None });
+            }
+            Event::SequenceStart(_) => {
+                let path = self.node_path(mark, None).unwrap_or_default();
+                self.stack.push(Frame::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.end_value();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn collects_located_problems() {
+        let schema = "
+object_type:
+  name: a
+  storage: s
+  deletion: by_anyy
+  id: id
+  edge_types:
+    - name: a_to_b
+      to:
+        object_type: b
+        feild: a_id
+      deletion: deep
+---
+object_type:
+  name: b
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let err = crate::read_yamls(schema, "storages: []").unwrap_err();
+        let located: Vec<(usize, &str, usize)> = err
+            .problems
+            .iter()
+            .map(|p| (p.document, p.path.as_str(), p.line))
+            .collect();
+
+        assert!(located.contains(&(0, "object_type.deletion", 5)));
+        assert!(located.contains(&(0, "object_type.edge_types[0].to.feild", 11)));
+        assert!(located.contains(&(0, "object_type.edge_types[0].to.field", 9)));
+        assert!(located.contains(&(1, "object_type.storage", 14)));
+    }
+
+    #[test]
+    fn collects_storage_problems() {
+        let schema = "object_type:\n  name: a\n  storage: s\n  deletion: directly\n  id: id\n  edge_types: []\n";
+        let config = "storages:\n  - name: s\n    plugin: mongo\n  - plugin: diesel\n    url: mysql://localhost\n";
+        let err = crate::read_yamls(schema, config).unwrap_err();
+        let paths: Vec<&str> = err.problems.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, vec!["storages[0].url", "storages[0].plugin", "storages[1].name"]);
+
+        let err = crate::read_yamls(schema, "checkpoints:\n  dir: c\n").unwrap_err();
+        assert_eq!(err.problems[0].path, "storages");
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let schema = "object_type:\n  name: a\n  deletion: : directly\n";
+        let err = crate::read_yamls(schema, "storages: []").unwrap_err();
+        assert_eq!(err.problems.len(), 1);
+        assert_eq!(err.problems[0].line, 3);
+    }
+
+    #[test]
+    fn example_schemas_load() {
+        let config = "storages: []";
+        assert!(crate::read_yamls(include_str!("../../examples/hotcrp/schema.yaml"), config).is_ok());
+        assert!(crate::read_yamls(include_str!("../../examples/hotcrp/schema_errors.yaml"), config).is_ok());
+    }
+}
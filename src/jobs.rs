@@ -0,0 +1,503 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use yaml_rust::Yaml;
+
+use crate::graph::schema::{check_keys, optional_u64, required_str, SchemaProblem};
+use crate::graph::parallel::panic_message;
+use crate::graph::checkpoint::Task;
+use crate::graph::hold::HeldInstance;
+use crate::graph::{DelfGraph, Deletion};
+use crate::store;
+
+const JOBS_KEYS: [&str; 6] = ["path", "workers", "max_attempts", "backoff_seconds", "poll_seconds", "retention_days"];
+
+/// The settings of the job queue, from the `jobs` section of the config
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobConfig {
+    /// The json file the queue is kept in
+    pub path: String,
+    /// The number of worker threads running jobs
+    pub workers: u64,
+    /// The number of times a job is tried before it is moved to the dead letter list
+    pub max_attempts: u64,
+    /// The wait before the first retry of a job, which doubles with each retry after
+    pub backoff_seconds: u64,
+    /// How often idle workers check the queue for jobs
+    pub poll_seconds: u64,
+    /// How long completed jobs are kept in the queue, in days
+    pub retention_days: u64,
+}
+
+impl JobConfig {
+    /// Read the `jobs` section of a config
+    pub(crate) fn from_yaml(yaml: &Yaml) -> Result<JobConfig, Vec<SchemaProblem>> {
+        let mut problems = Vec::new();
+        check_keys(yaml, &JOBS_KEYS, &mut problems);
+        let path = required_str(yaml, "path", &mut problems);
+        let workers = optional_u64(yaml, "workers", &mut problems).unwrap_or(4);
+        let max_attempts = optional_u64(yaml, "max_attempts", &mut problems).unwrap_or(8);
+        let backoff_seconds = optional_u64(yaml, "backoff_seconds", &mut problems).unwrap_or(1);
+        let poll_seconds = optional_u64(yaml, "poll_seconds", &mut problems).unwrap_or(1);
+        let retention_days = optional_u64(yaml, "retention_days", &mut problems).unwrap_or(7);
+
+        if workers == 0 {
+            problems.push(SchemaProblem::new(
+                "workers",
+                String::from("`workers` must be at least 1"),
+            ));
+        }
+        if max_attempts == 0 {
+            problems.push(SchemaProblem::new(
+                "max_attempts",
+                String::from("`max_attempts` must be at least 1"),
+            ));
+        }
+
+        if problems.len() > 0 {
+            return Err(problems);
+        }
+
+        Ok(JobConfig {
+            path: path.unwrap(),
+            workers,
+            max_attempts,
+            backoff_seconds,
+            poll_seconds,
+            retention_days,
+        })
+    }
+}
+
+/// The deletion a job runs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobTarget {
+    /// Delete an object instance
+    Object { object_type: String, id: String },
+    /// Delete an edge instance
+    Edge {
+        edge_type: String,
+        from_id: String,
+        to_id: String,
+    },
+}
+
+/// Where a job is in its life
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting to be run, or to be retried
+    Pending,
+    /// Being run by a worker
+    Running,
+    /// Completed
+    Done,
+    /// Failed on every attempt, and is in the dead letter list
+    Dead,
+}
+
+/// A deletion in the job queue
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    /// The unique id of the job
+    pub id: String,
+    /// The deletion the job runs
+    pub target: JobTarget,
+    /// Where the job is in its life
+    pub status: JobStatus,
+    /// The number of times the job has been tried
+    pub attempts: u64,
+    /// When the job was added to the queue, in seconds since the unix epoch
+    pub created: u64,
+    /// The earliest time the job can next be run, in seconds since the unix epoch
+    pub next_attempt: u64,
+    /// The error from the last failed attempt
+    pub last_error: Option<String>,
+    /// The id to restore the deletion with, once done, if a restoration log is configured
+    pub deletion_id: Option<String>,
+    /// The instances the deletion skipped because they are under a legal hold, once done
+    #[serde(default)]
+    pub held: Vec<HeldInstance>,
+    /// When the job completed, in seconds since the unix epoch
+    #[serde(default)]
+    pub finished: Option<u64>,
+    /// The id of the checkpoint the job's cascade is kept in, so a retry finishes what a failed attempt left
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+}
+
+/// A durable queue of deletion jobs, kept in a json file.  Jobs are run by a pool of worker threads and retried with exponential backoff until they complete, or are moved to the dead letter list after `max_attempts` failures.  Completed jobs are dropped from the queue once they are older than `retention_days`.
+#[derive(Debug)]
+pub struct JobQueue {
+    pub config: JobConfig,
+    jobs: Mutex<Vec<Job>>,
+}
+
+impl JobQueue {
+    /// Open the queue in the configured file.  Jobs which were running when the process stopped are run again.
+    pub fn open(config: JobConfig) -> Result<JobQueue, String> {
+        let mut jobs: Vec<Job> = store::load(&config.path)?;
+        for job in jobs.iter_mut() {
+            if job.status == JobStatus::Running {
+                job.status = JobStatus::Pending;
+            }
+        }
+        prune(&mut jobs, &config);
+        store::save(&config.path, &jobs)?;
+
+        Ok(JobQueue {
+            config,
+            jobs: Mutex::new(jobs),
+        })
+    }
+
+    /// Add a deletion to the queue, returning the new job
+    pub fn enqueue(&self, target: JobTarget) -> Result<Job, String> {
+        let now = store::now();
+        let job = Job {
+            id: store::new_id(),
+            target,
+            status: JobStatus::Pending,
+            attempts: 0,
+            created: now,
+            next_attempt: now,
+            last_error: None,
+            deletion_id: None,
+            held: Vec::new(),
+            finished: None,
+            checkpoint: None,
+        };
+        self.update(|jobs| {
+            jobs.push(job.clone());
+            return true;
+        })?;
+        return Ok(job);
+    }
+
+    /// Get a job by id
+    pub fn get(&self, id: &String) -> Option<Job> {
+        let jobs = self.jobs.lock().unwrap();
+        return jobs.iter().find(|j| j.id == *id).cloned();
+    }
+
+    /// The jobs which failed on every attempt
+    pub fn dead_letters(&self) -> Vec<Job> {
+        let jobs = self.jobs.lock().unwrap();
+        return jobs
+            .iter()
+            .filter(|j| j.status == JobStatus::Dead)
+            .cloned()
+            .collect();
+    }
+
+    /// Move a job from the dead letter list back into the queue to be tried again
+    pub fn retry(&self, id: &String) -> Result<Job, String> {
+        let mut retried = None;
+        self.update(|jobs| {
+            if let Some(job) = jobs
+                .iter_mut()
+                .find(|j| j.id == *id && j.status == JobStatus::Dead)
+            {
+                job.status = JobStatus::Pending;
+                job.attempts = 0;
+                job.next_attempt = store::now();
+                retried = Some(job.clone());
+            }
+            return retried.is_some();
+        })?;
+        match retried {
+            Some(job) => Ok(job),
+            None => Err(format!("No dead job with id {}", id)),
+        }
+    }
+
+    // Take the next job which is due to run, marking it as running
+    fn claim(&self) -> Result<Option<Job>, String> {
+        let now = store::now();
+        let mut claimed = None;
+        self.update(|jobs| {
+            if let Some(job) = jobs
+                .iter_mut()
+                .find(|j| j.status == JobStatus::Pending && j.next_attempt <= now)
+            {
+                job.status = JobStatus::Running;
+                job.attempts += 1;
+                if job.checkpoint.is_none() {
+                    job.checkpoint = Some(store::new_id());
+                }
+                claimed = Some(job.clone());
+            }
+            return claimed.is_some();
+        })?;
+        return Ok(claimed);
+    }
+
+    // Record the outcome of running a job
+    fn finish(&self, id: &String, result: Result<Deletion, String>) -> Result<(), String> {
+        let config = &self.config;
+        self.update(|jobs| {
+            let job = match jobs.iter_mut().find(|j| j.id == *id) {
+                Some(job) => job,
+                None => return false,
+            };
+            match result {
+                Ok(deletion) => {
+                    job.status = JobStatus::Done;
+                    job.deletion_id = deletion.deletion_id;
+                    job.held = deletion.held;
+                    job.last_error = None;
+                    job.finished = Some(store::now());
+                }
+                Err(e) => {
+                    job.last_error = Some(e);
+                    if job.attempts >= config.max_attempts {
+                        job.status = JobStatus::Dead;
+                    } else {
+                        let backoff = config.backoff_seconds << (job.attempts - 1).min(32);
+                        job.status = JobStatus::Pending;
+                        job.next_attempt = store::now() + backoff;
+                    }
+                }
+            }
+            return true;
+        })
+    }
+
+    // Change the jobs, and if `f` returns that it changed anything, drop the expired jobs and save them
+    fn update<F>(&self, f: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut Vec<Job>) -> bool,
+    {
+        let mut jobs = self.jobs.lock().unwrap();
+        if !f(&mut jobs) {
+            return Ok(());
+        }
+        prune(&mut jobs, &self.config);
+        return store::save(&self.config.path, &*jobs);
+    }
+}
+
+// Drop the completed jobs which finished longer ago than the retention period
+fn prune(jobs: &mut Vec<Job>, config: &JobConfig) {
+    let expired_before = store::now().saturating_sub(config.retention_days * 24 * 60 * 60);
+    jobs.retain(|j| match j.finished {
+        Some(finished) if j.status == JobStatus::Done => finished >= expired_before,
+        _ => true,
+    });
+}
+
+/// Run a job's deletion on the graph, checkpointed under the job's checkpoint id.  If an earlier attempt left its cascade unfinished, the cascade is resumed from the checkpoint instead of starting again.  A storage error panics, so the panic is caught and returned as the error.
+pub fn run_job(graph: &DelfGraph, job: &Job) -> Result<Deletion, String> {
+    let checkpoint = job.checkpoint.as_ref();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        if let Some(id) = checkpoint {
+            if let Some(deletion) = graph.resume_cascade(id)? {
+                return Ok(deletion);
+            }
+        }
+        match &job.target {
+            JobTarget::Object { object_type, id } => {
+                if !graph.nodes.contains_key(object_type) {
+                    return Err(format!("Unknown object type: {}", object_type));
+                }
+                let task = Task::Object {
+                    object: object_type.clone(),
+                    id: id.clone(),
+                };
+                graph.delete_task(task, checkpoint)
+            }
+            JobTarget::Edge {
+                edge_type,
+                from_id,
+                to_id,
+            } => {
+                if !graph.edges.contains_key(edge_type) {
+                    return Err(format!("Unknown edge type: {}", edge_type));
+                }
+                let task = Task::EdgeOne {
+                    edge: edge_type.clone(),
+                    from_id: from_id.clone(),
+                    to_id: to_id.clone(),
+                };
+                graph.delete_task(task, checkpoint)
+            }
+        }
+    }));
+
+    match result {
+        Ok(r) => r,
+        Err(panic) => Err(panic_message(panic)),
+    }
+}
+
+/// Spawn the configured number of worker threads, each with its own connections to the storages, to run the jobs in the queue
+pub fn start_workers(schema_path: &String, config_path: &String, queue: Arc<JobQueue>) {
+    println!(
+        "Starting {} worker(s) running deletion jobs ... ",
+        queue.config.workers
+    );
+    for _ in 0..queue.config.workers {
+        let s_path = schema_path.clone();
+        let c_path = config_path.clone();
+        let queue = queue.clone();
+        thread::spawn(move || {
+            let poll_duration = Duration::from_secs(queue.config.poll_seconds);
+            let mut graph = None;
+            loop {
+                if graph.is_none() {
+                    // a failed job may have left the connections unusable, so they're made again
+                    graph = match crate::read_files(&s_path, &c_path) {
+                        Ok(graph) => Some(graph),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return;
+                        }
+                    };
+                }
+
+                let job = match queue.claim() {
+                    Ok(Some(job)) => job,
+                    Ok(None) => {
+                        thread::sleep(poll_duration);
+                        continue;
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        thread::sleep(poll_duration);
+                        continue;
+                    }
+                };
+
+                let result = run_job(graph.as_ref().unwrap(), &job);
+                if result.is_err() {
+                    graph = None;
+                }
+                if let Err(e) = queue.finish(&job.id, result) {
+                    eprintln!("{}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::checkpoint::Checkpoint;
+
+    #[test]
+    fn retries_then_dead_letters() {
+        let path = std::env::temp_dir()
+            .join(format!("delf_jobs_{}.json", store::new_id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let config = JobConfig {
+            path: path.clone(),
+            workers: 1,
+            max_attempts: 2,
+            backoff_seconds: 0,
+            poll_seconds: 1,
+            retention_days: 7,
+        };
+        let queue = JobQueue::open(config.clone()).unwrap();
+        let job = queue
+            .enqueue(JobTarget::Object {
+                object_type: String::from("a"),
+                id: String::from("1"),
+            })
+            .unwrap();
+
+        let claimed = queue.claim().unwrap().unwrap();
+        assert_eq!(claimed.id, job.id);
+        assert_eq!(queue.claim().unwrap(), None);
+        queue.finish(&job.id, Err(String::from("down"))).unwrap();
+        assert_eq!(queue.get(&job.id).unwrap().status, JobStatus::Pending);
+
+        // a restarted queue picks up where the last left off
+        let queue = JobQueue::open(config.clone()).unwrap();
+        queue.claim().unwrap().unwrap();
+        queue.finish(&job.id, Err(String::from("down"))).unwrap();
+        assert_eq!(queue.dead_letters().len(), 1);
+
+        queue.retry(&job.id).unwrap();
+        queue.claim().unwrap().unwrap();
+        queue.finish(&job.id, Ok(Deletion::default())).unwrap();
+        assert_eq!(queue.get(&job.id).unwrap().status, JobStatus::Done);
+
+        // completed jobs are dropped once they're past the retention period
+        let mut jobs = vec![queue.get(&job.id).unwrap()];
+        prune(&mut jobs, &config);
+        assert_eq!(jobs.len(), 1);
+        jobs[0].finished = Some(0);
+        prune(&mut jobs, &config);
+        assert!(jobs.is_empty());
+
+        // an idle worker doesn't rewrite the queue
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(queue.claim().unwrap(), None);
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn retry_finishes_the_failed_cascade() {
+        let dir = std::env::temp_dir().join(format!("delf_job_checkpoints_{}", store::new_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = format!(
+            "storages: []\njobs:\n  path: {}\ncheckpoints:\n  dir: {}\n",
+            dir.join("jobs.json").to_str().unwrap(),
+            dir.to_str().unwrap()
+        );
+        let schema = "
+object_type:
+  name: contact
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: contact_review
+      to:
+        object_type: review
+        field: contact_id
+      deletion: deep
+---
+object_type:
+  name: review
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+        let (graph, memory) = crate::graph::test_graph_with_config(schema, &config);
+        memory.insert("review", &[("id", "20"), ("contact_id", "1")]);
+        let queue = JobQueue::open(graph.jobs.clone().unwrap()).unwrap();
+        queue
+            .enqueue(JobTarget::Object {
+                object_type: String::from("contact"),
+                id: String::from("1"),
+            })
+            .unwrap();
+        let job = queue.claim().unwrap().unwrap();
+
+        // the first attempt failed after the contact was deleted, but before its reviews were
+        let checkpoint = Checkpoint {
+            id: job.checkpoint.clone().unwrap(),
+            pending: vec![Task::EdgeAll {
+                edge: String::from("contact_review"),
+                from_id: String::from("1"),
+            }],
+            ..Checkpoint::default()
+        };
+        checkpoint.save(graph.checkpoints.as_ref().unwrap()).unwrap();
+
+        assert!(run_job(&graph, &job).is_ok());
+        assert_eq!(memory.column("review", "id").len(), 0);
+        assert_eq!(Checkpoint::load(graph.checkpoints.as_ref().unwrap(), &checkpoint.id), Ok(None));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use std::process::exit;
+
 use clap::Clap;
 
 use delf;
@@ -18,6 +20,127 @@ struct Opts {
 enum SubCommand {
     Validate,
     Run,
+    Explain(Explain),
+    Plan(Plan),
+    Restore(Restore),
+    Hold(Hold),
+    Schedule(Schedule),
+}
+
+/// Place, release, or list legal holds, which keep object instances from being deleted
+#[derive(Clap)]
+struct Hold {
+    #[clap(subcommand)]
+    action: HoldAction,
+}
+
+#[derive(Clap)]
+enum HoldAction {
+    Place(PlaceHold),
+    Release(ReleaseHold),
+    List,
+}
+
+/// Place a legal hold on an object instance
+#[derive(Clap)]
+struct PlaceHold {
+    /// The name of the object type to hold
+    object_type: String,
+    /// The id of the instance to hold
+    id: String,
+    /// Why the hold is placed
+    #[clap(short, long)]
+    reason: Option<String>,
+}
+
+/// Release the legal hold on an object instance
+#[derive(Clap)]
+struct ReleaseHold {
+    /// The name of the held object type
+    object_type: String,
+    /// The id of the held instance
+    id: String,
+}
+
+/// Schedule, cancel, or list deletions to be run at a later time
+#[derive(Clap)]
+struct Schedule {
+    #[clap(subcommand)]
+    action: ScheduleAction,
+}
+
+#[derive(Clap)]
+enum ScheduleAction {
+    Add(AddSchedule),
+    Cancel(CancelSchedule),
+    List,
+}
+
+/// Schedule the deletion of an object instance
+#[derive(Clap)]
+struct AddSchedule {
+    /// The name of the object type to delete
+    object_type: String,
+    /// The id of the instance to delete
+    id: String,
+    /// When to delete the instance, in seconds since the unix epoch
+    at: u64,
+}
+
+/// Cancel the scheduled deletion of an object instance
+#[derive(Clap)]
+struct CancelSchedule {
+    /// The name of the scheduled object type
+    object_type: String,
+    /// The id of the scheduled instance
+    id: String,
+}
+
+/// Restore the rows removed by a deletion from the restoration log
+#[derive(Clap)]
+struct Restore {
+    /// The id of the deletion, as returned when it was made
+    deletion_id: String,
+}
+
+/// Explain how an object gets deleted, or why it can't be
+#[derive(Clap)]
+struct Explain {
+    /// The name of the object type to explain
+    object_type: String,
+}
+
+/// Print what a deletion would remove, without deleting anything
+#[derive(Clap)]
+struct Plan {
+    #[clap(subcommand)]
+    target: PlanTarget,
+}
+
+#[derive(Clap)]
+enum PlanTarget {
+    Object(PlanObject),
+    Edge(PlanEdge),
+}
+
+/// Plan the deletion of an object instance
+#[derive(Clap)]
+struct PlanObject {
+    /// The name of the object type to delete
+    object_type: String,
+    /// The id of the instance to delete
+    id: String,
+}
+
+/// Plan the deletion of an edge instance
+#[derive(Clap)]
+struct PlanEdge {
+    /// The name of the edge type to delete
+    edge_type: String,
+    /// The id of the object instance the edge starts from
+    from_id: String,
+    /// The id of the object instance the edge points to
+    to_id: String,
 }
 
 fn main() {
@@ -32,15 +155,139 @@ fn main() {
             println!("Starting delf api...");
             run(&opts.schema, &opts.config);
         }
+        SubCommand::Explain(e) => {
+            explain(&opts.schema, &opts.config, &e.object_type);
+        }
+        SubCommand::Plan(p) => {
+            plan(&opts.schema, &opts.config, &p.target);
+        }
+        SubCommand::Restore(r) => {
+            restore(&opts.schema, &opts.config, &r.deletion_id);
+        }
+        SubCommand::Hold(h) => {
+            hold(&opts.schema, &opts.config, &h.action);
+        }
+        SubCommand::Schedule(s) => {
+            schedule(&opts.schema, &opts.config, &s.action);
+        }
     }
 }
 
 fn validate(schema_path: &String, config_path: &String) {
-    let graph = delf::read_files(schema_path, config_path);
+    let graph = read_graph(schema_path, config_path);
     graph.validate();
 }
 
+fn explain(schema_path: &String, config_path: &String, object_type: &String) {
+    let graph = read_graph(schema_path, config_path);
+    match graph.explain(object_type) {
+        Ok(explanation) => explanation.print(),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn plan(schema_path: &String, config_path: &String, target: &PlanTarget) {
+    let graph = read_graph(schema_path, config_path);
+    let deletion_plan = match target {
+        PlanTarget::Object(o) => graph.plan_delete_object(&o.object_type, &o.id),
+        PlanTarget::Edge(e) => graph.plan_delete_edge(&e.edge_type, &e.from_id, &e.to_id),
+    };
+    match deletion_plan {
+        Ok(deletion_plan) => deletion_plan.print(),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn restore(schema_path: &String, config_path: &String, deletion_id: &String) {
+    let graph = read_graph(schema_path, config_path);
+    match graph.restore(deletion_id) {
+        Ok(restored) => println!("Restored {} row(s) from deletion {}", restored, deletion_id),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
 fn run(schema_path: &String, config_path: &String) {
+    let api = match delf::init_api(schema_path, config_path) {
+        Ok(api) => api,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
     delf::check_short_ttl_loop(schema_path, config_path);
-    delf::init_api(schema_path, config_path).launch();
+    delf::scheduled_deletions_loop(schema_path, config_path);
+    api.launch();
+}
+
+fn hold(schema_path: &String, config_path: &String, action: &HoldAction) {
+    let graph = read_graph(schema_path, config_path);
+    let result = match action {
+        HoldAction::Place(p) => graph
+            .place_hold(&p.object_type, &p.id, p.reason.clone())
+            .map(|h| println!("Held {} {}", h.object_type, h.id)),
+        HoldAction::Release(r) => graph
+            .release_hold(&r.object_type, &r.id)
+            .map(|h| println!("Released {} {}", h.object_type, h.id)),
+        HoldAction::List => graph.holds().map(|holds| {
+            for h in holds.iter() {
+                match &h.reason {
+                    Some(reason) => println!("{} {}: {}", h.object_type, h.id, reason),
+                    None => println!("{} {}", h.object_type, h.id),
+                }
+            }
+        }),
+    };
+    match result {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn schedule(schema_path: &String, config_path: &String, action: &ScheduleAction) {
+    let graph = read_graph(schema_path, config_path);
+    let result = match action {
+        ScheduleAction::Add(a) => graph
+            .schedule_deletion(&a.object_type, &a.id, a.at)
+            .map(|s| println!("Scheduled {} {} for {}", s.object_type, s.id, s.due)),
+        ScheduleAction::Cancel(c) => graph
+            .cancel_deletion(&c.object_type, &c.id)
+            .map(|s| println!("Cancelled {} {}", s.object_type, s.id)),
+        ScheduleAction::List => graph.scheduled_deletions().map(|scheduled| {
+            for s in scheduled.iter() {
+                match &s.error {
+                    Some(error) => println!("{} {} at {}: failed, {}", s.object_type, s.id, s.due, error),
+                    None => println!("{} {} at {}", s.object_type, s.id, s.due),
+                }
+            }
+        }),
+    };
+    match result {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn read_graph(schema_path: &String, config_path: &String) -> delf::graph::DelfGraph {
+    match delf::read_files(schema_path, config_path) {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
 }
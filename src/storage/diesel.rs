@@ -1,5 +1,3 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use diesel;
 use diesel::sql_types::{BigInt, Integer, Text};
 use diesel::Connection;
@@ -7,7 +5,12 @@ use diesel::QueryableByName;
 use diesel::RunQueryDsl;
 
 pub use super::DelfStorageConnection;
-use crate::graph::{edge::DelfEdge, object::DelfObject};
+use super::Row;
+use crate::graph::{
+    edge::{self, DelfEdge, ListFormat},
+    object::{Anonymization, DeleteType, DelfObject, Replacement},
+    predicate::Predicate,
+};
 
 pub struct DieselConnection {
     connection: diesel::mysql::MysqlConnection,
@@ -38,6 +41,18 @@ struct ValidationResult {
     count: i64,
 }
 
+#[derive(QueryableByName)]
+struct ColumnResult {
+    #[sql_type = "Text"]
+    column_name: String,
+}
+
+#[derive(QueryableByName)]
+struct RowResult {
+    #[sql_type = "Text"]
+    row_json: String,
+}
+
 impl DelfStorageConnection for DieselConnection {
     fn connect(database_url: &str) -> DieselConnection {
         let raw_connection = diesel::mysql::MysqlConnection::establish(database_url);
@@ -55,12 +70,14 @@ impl DelfStorageConnection for DieselConnection {
         table: &String,
         id_field: &String,
         id_type: &String,
+        predicate: Option<&Predicate>,
     ) -> Vec<String> {
         let mut query_str = format!(
             "SELECT {} as id_field FROM {} WHERE {} = ",
             id_field, table, edge_field
         );
-        self.append_id_to_query(&mut query_str, from_id_type, from_id);
+        append_id_to_query(&mut query_str, from_id_type, from_id);
+        append_predicate(&mut query_str, predicate);
 
         let query = diesel::sql_query(query_str);
 
@@ -91,14 +108,11 @@ impl DelfStorageConnection for DieselConnection {
         time_field: &String,
         id_field: &String,
         id_type: &String,
+        before: u64,
     ) -> Vec<String> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
         let query_str = format!(
             "SELECT {} as id_field FROM {} WHERE {} < {:?}",
-            id_field, table, time_field, now
+            id_field, table, time_field, before
         );
 
         let query = diesel::sql_query(query_str);
@@ -124,22 +138,75 @@ impl DelfStorageConnection for DieselConnection {
         return obj_ids;
     }
 
+    fn get_soft_deleted_ids(&self, obj: &DelfObject, field: &String, before: u64) -> Vec<String> {
+        let query_str = format!(
+            "SELECT {} as id_field FROM {} WHERE {} < FROM_UNIXTIME({})",
+            obj.id_field, obj.name, field, before
+        );
+        return self.load_ids(query_str, &obj.id_type);
+    }
+
+    fn get_object_ids_matching(
+        &self,
+        table: &String,
+        id_field: &String,
+        id_type: &String,
+        predicate: &Predicate,
+    ) -> Vec<String> {
+        let query_str = format!(
+            "SELECT {} as id_field FROM {} WHERE {}",
+            id_field,
+            table,
+            predicate.to_sql()
+        );
+        return self.load_ids(query_str, id_type);
+    }
+
+    fn get_object_ids_page(
+        &self,
+        table: &String,
+        id_field: &String,
+        id_type: &String,
+        predicate: &Predicate,
+        after: Option<&String>,
+        limit: usize,
+    ) -> Vec<String> {
+        let mut query_str = format!(
+            "SELECT {} as id_field FROM {} WHERE ({})",
+            id_field,
+            table,
+            predicate.to_sql()
+        );
+        if let Some(after) = after {
+            query_str.push_str(format!(" AND {} > ", id_field).as_str());
+            append_id_to_query(&mut query_str, id_type, after);
+        }
+        query_str.push_str(format!(" ORDER BY {} LIMIT {}", id_field, limit).as_str());
+        return self.load_ids(query_str, id_type);
+    }
+
     fn delete_edge(
         &self,
+        from: &DelfObject,
         to: &DelfObject,
         from_id: &String,
         to_id: Option<&String>,
         edge: &DelfEdge,
     ) -> bool {
-        match &edge.to.mapping_table {
-            Some(map_table) => self.delete_indirect_edge(edge, to, from_id, to_id, map_table), // delete the id pair from the mapping table
-            None => self.delete_direct_edge(to, from_id, edge), // try to set null in object table
+        let num_rows = diesel::sql_query(delete_edge_query(from, to, from_id, to_id, edge))
+            .execute(&self.connection)
+            .unwrap();
+
+        if num_rows == 0 {
+            return false;
+        } else {
+            return true;
         }
     }
 
     fn delete_object(&self, obj: &DelfObject, id: &String) -> bool {
         let mut query_str = format!("DELETE FROM {} WHERE {} = ", obj.name, obj.id_field,);
-        self.append_id_to_query(&mut query_str, &obj.id_type, id);
+        append_id_to_query(&mut query_str, &obj.id_type, id);
 
         let num_rows = diesel::sql_query(query_str)
             .execute(&self.connection)
@@ -152,25 +219,112 @@ impl DelfStorageConnection for DieselConnection {
         }
     }
 
-    fn validate_edge(&self, edge: &DelfEdge) -> Result<(), String> {
-        let table: &str;
-        match &edge.to.mapping_table {
-            Some(map_table) => {
-                table = map_table;
-            }
-            None => {
-                table = &edge.to.object_type;
-            }
+    fn soft_delete_object(&self, obj: &DelfObject, id: &String, field: &String, time: u64) -> bool {
+        let mut query_str = format!("UPDATE {} SET {} = FROM_UNIXTIME({}) WHERE {} IS NULL AND {} = ", obj.name, field, time, field, obj.id_field);
+        append_id_to_query(&mut query_str, &obj.id_type, id);
+
+        let num_rows = diesel::sql_query(query_str)
+            .execute(&self.connection)
+            .unwrap();
+
+        if num_rows == 0 {
+            return false;
+        } else {
+            return true;
+        }
+    }
+
+    fn anonymize_object(&self, obj: &DelfObject, id: &String, fields: &Vec<Anonymization>) -> bool {
+        let sets: Vec<String> = fields
+            .iter()
+            .map(|a| match &a.replacement {
+                Replacement::Null => format!("{} = NULL", a.field),
+                Replacement::Constant(value) => format!(
+                    "{} = {}",
+                    a.field,
+                    sql_literal(&serde_json::Value::String(value.clone()))
+                ),
+                Replacement::Hash => format!("{} = SHA2(CAST({} AS CHAR), 256)", a.field, a.field),
+            })
+            .collect();
+        let mut query_str = format!(
+            "UPDATE {} SET {} WHERE {} = ",
+            obj.name,
+            sets.join(", "),
+            obj.id_field
+        );
+        append_id_to_query(&mut query_str, &obj.id_type, id);
+
+        let num_rows = diesel::sql_query(query_str)
+            .execute(&self.connection)
+            .unwrap();
+
+        if num_rows == 0 {
+            return false;
+        } else {
+            return true;
         }
+    }
+
+    fn validate_edge(&self, edge: &DelfEdge) -> Result<(), String> {
+        let table = edge.table();
         let res = diesel::sql_query(format!(
             "SELECT count({}) as count FROM {}",
-            edge.to.field, table
+            edge.field(), table
         ))
         .load::<ValidationResult>(&self.connection);
 
-        match res {
-            Ok(_) => return Ok(()),
-            Err(_) => return Err(format!("Edge {} doesn't match database schema", edge.name)),
+        if res.is_err() {
+            return Err(format!("Edge {} doesn't match database schema", edge.name));
+        }
+
+        if let Some(hops) = &edge.to.path {
+            for hop in hops.iter() {
+                let columns = self.columns(&hop.table);
+                let missing: Vec<&String> = [&hop.from, &hop.to]
+                    .iter()
+                    .filter(|c| !columns.contains(c))
+                    .cloned()
+                    .collect();
+                if missing.len() > 0 {
+                    return Err(format!(
+                        "Edge {} has a path through {}, which doesn't have the columns: {}",
+                        edge.name,
+                        hop.table,
+                        missing.iter().map(|c| c.as_str()).collect::<Vec<&str>>().join(", ")
+                    ));
+                }
+            }
+        }
+
+        if let Some(d) = &edge.to.discriminator {
+            if !self.columns(table).contains(&d.column) {
+                return Err(format!(
+                    "Edge {} has a discriminator column {} which isn't in {}",
+                    edge.name, d.column, table
+                ));
+            }
+        }
+
+        match &edge.predicate {
+            Some(predicate) => {
+                let columns = self.columns(table);
+                let missing: Vec<String> = predicate
+                    .columns()
+                    .into_iter()
+                    .filter(|c| !columns.contains(c))
+                    .collect();
+                if missing.len() > 0 {
+                    return Err(format!(
+                        "Edge {} has a `where` predicate on columns not in {}: {}",
+                        edge.name,
+                        table,
+                        missing.join(", ")
+                    ));
+                }
+                return Ok(());
+            }
+            None => return Ok(()),
         }
     }
 
@@ -181,15 +335,48 @@ impl DelfStorageConnection for DieselConnection {
         ))
         .load::<ValidationResult>(&self.connection);
 
-        match res {
-            Ok(_) => return Ok(()),
-            Err(_) => return Err(format!("Object {} doesn't match database schema", obj.name)),
+        if res.is_err() {
+            return Err(format!("Object {} doesn't match database schema", obj.name));
         }
+
+        // the fields anonymized or soft deleted with must be in the table
+        let mut fields = Vec::new();
+        if let DeleteType::Anonymize(anonymized) = &obj.deletion {
+            fields.extend(anonymized.iter().map(|a| a.field.clone()));
+        }
+        if let Some(soft_delete) = &obj.soft_delete {
+            fields.push(soft_delete.field.clone());
+        }
+        if fields.len() == 0 {
+            return Ok(());
+        }
+
+        let columns = self.columns(&obj.name);
+        let missing: Vec<String> = fields.into_iter().filter(|f| !columns.contains(f)).collect();
+        if missing.len() > 0 {
+            return Err(format!(
+                "Object {} uses fields not in its table: {}",
+                obj.name,
+                missing.join(", ")
+            ));
+        }
+
+        // instances are marked with the date and time they were deleted
+        if let Some(soft_delete) = &obj.soft_delete {
+            let data_type = self.column_type(&obj.name, &soft_delete.field);
+            if data_type != "datetime" && data_type != "timestamp" {
+                return Err(format!(
+                    "Object {} soft deletes with {}, which is a {} rather than a datetime or timestamp",
+                    obj.name, soft_delete.field, data_type
+                ));
+            }
+        }
+        return Ok(());
     }
 
     fn has_edge(&self, obj: &DelfObject, id: &String, edge: &DelfEdge) -> bool {
-        if edge.to.mapping_table.is_some() {
-            return false;
+        if let Some(map_table) = &edge.to.mapping_table {
+            return self.has_mapping_row(obj, id, edge, map_table);
         }
         let default_value;
         match obj.id_type.to_lowercase().as_str() {
@@ -202,7 +389,9 @@ impl DelfStorageConnection for DieselConnection {
             "SELECT count(*) as count FROM {} WHERE {} <> {} AND {} = ",
             obj.name, edge.to.field, default_value, obj.id_field
         );
-        self.append_id_to_query(&mut query_str, &obj.id_type, id);
+        append_id_to_query(&mut query_str, &obj.id_type, id);
+        append_predicate(&mut query_str, edge.row_predicate().as_ref());
+        append_predicate(&mut query_str, obj.live_predicate().as_ref());
         let res = diesel::sql_query(query_str)
             .load::<ValidationResult>(&self.connection)
             .unwrap();
@@ -213,70 +402,466 @@ impl DelfStorageConnection for DieselConnection {
             return false;
         }
     }
-}
 
-impl DieselConnection {
-    fn delete_indirect_edge(
+    fn has_object(&self, obj: &DelfObject, id: &String) -> bool {
+        let mut query_str = format!(
+            "SELECT count(*) as count FROM {} WHERE {} = ",
+            obj.name, obj.id_field
+        );
+        append_id_to_query(&mut query_str, &obj.id_type, id);
+        append_predicate(&mut query_str, obj.live_predicate().as_ref());
+        let res = diesel::sql_query(query_str)
+            .load::<ValidationResult>(&self.connection)
+            .unwrap();
+
+        if res[0].count > 0 {
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    fn get_rows(
         &self,
-        edge: &DelfEdge,
-        to: &DelfObject,
-        from_id: &String,
-        to_id: Option<&String>,
         table: &String,
-    ) -> bool {
-        let num_rows = match to_id {
-            Some(id) => {
-                // TODO make this not panic on a string id
-                diesel::sql_query(format!(
-                    "DELETE FROM {} WHERE {} = {} AND {} = {}",
-                    table, to.id_field, id, edge.to.field, from_id
-                ))
-                .execute(&self.connection)
-                .unwrap()
-            }
-            None => diesel::sql_query(format!(
-                "DELETE FROM {} WHERE {} = {}",
-                table, edge.to.field, from_id
-            ))
-            .execute(&self.connection)
-            .unwrap(),
-        };
+        field: &String,
+        value_type: &String,
+        value: &String,
+        predicate: Option<&Predicate>,
+    ) -> Vec<Row> {
+        let pairs: Vec<String> = self
+            .columns(table)
+            .iter()
+            .map(|c| format!("'{}', {}", c, c))
+            .collect();
+        let mut query_str = format!(
+            "SELECT JSON_OBJECT({}) as row_json FROM {} WHERE {} = ",
+            pairs.join(", "),
+            table,
+            field
+        );
+        append_id_to_query(&mut query_str, value_type, value);
+        append_predicate(&mut query_str, predicate);
 
-        if num_rows == 0 {
-            return false;
-        } else {
-            return true;
+        let res = diesel::sql_query(query_str)
+            .load::<RowResult>(&self.connection)
+            .unwrap();
+
+        let mut rows = Vec::new();
+        for r in res {
+            rows.push(serde_json::from_str(&r.row_json).unwrap());
         }
+        return rows;
     }
 
-    fn delete_direct_edge(&self, to: &DelfObject, from_id: &String, edge: &DelfEdge) -> bool {
-        let default_value;
-        match to.id_type.to_lowercase().as_str() {
-            "string" => default_value = "''",
-            "number" => default_value = "0",
-            _ => panic!("Unrecognized id type"),
+    fn insert_rows(&self, table: &String, rows: &Vec<Row>) -> usize {
+        if rows.len() == 0 {
+            return 0;
         }
+        let columns: Vec<&str> = rows[0].keys().map(|c| c.as_str()).collect();
+        let mut values = Vec::new();
+        for row in rows.iter() {
+            let literals: Vec<String> = columns.iter().map(|c| sql_literal(&row[*c])).collect();
+            values.push(format!("({})", literals.join(", ")));
+        }
+
+        return diesel::sql_query(format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            table,
+            columns.join(", "),
+            values.join(", ")
+        ))
+        .execute(&self.connection)
+        .unwrap();
+    }
+
+    fn columns(&self, table: &String) -> Vec<String> {
+        return diesel::sql_query(format!(
+            "SELECT COLUMN_NAME as column_name FROM information_schema.columns WHERE table_schema = DATABASE() AND table_name = '{}' ORDER BY ORDINAL_POSITION",
+            table
+        ))
+        .load::<ColumnResult>(&self.connection)
+        .unwrap()
+        .into_iter()
+        .map(|c| c.column_name)
+        .collect();
+    }
+
+    fn update_field(
+        &self,
+        table: &String,
+        id_field: &String,
+        field: &String,
+        rows: &Vec<Row>,
+    ) -> usize {
+        let mut num_rows = 0;
+        for row in rows.iter() {
+            num_rows += diesel::sql_query(format!(
+                "UPDATE {} SET {} = {} WHERE {} = {}",
+                table,
+                field,
+                sql_literal(&row[field]),
+                id_field,
+                sql_literal(&row[id_field])
+            ))
+            .execute(&self.connection)
+            .unwrap();
+        }
+        return num_rows;
+    }
+}
+
+// The SQL deleting the instances of an edge from `from_id`, or only the one to `to_id` if given
+fn delete_edge_query(
+    from: &DelfObject,
+    to: &DelfObject,
+    from_id: &String,
+    to_id: Option<&String>,
+    edge: &DelfEdge,
+) -> String {
+    match (&edge.to.mapping_table, &edge.to.path) {
+        (Some(map_table), _) => delete_indirect_edge_query(edge, &from.id_type, &to.id_field, &to.id_type, from_id, to_id, map_table), // delete the id pair from the mapping table
+        // delete the rows of the first table of the path, leading to `to_id` in the next table if given
+        (None, Some(hops)) => delete_indirect_edge_query(edge, &from.id_type, &hops[0].to, &hops[0].id_type, from_id, to_id, &hops[0].table),
+        (None, None) => delete_direct_edge_query(from, to, from_id, to_id, edge), // try to set null in object table
+    }
+}
+
+fn delete_indirect_edge_query(
+    edge: &DelfEdge,
+    from_id_type: &String,
+    to_field: &String,
+    to_id_type: &String,
+    from_id: &String,
+    to_id: Option<&String>,
+    table: &String,
+) -> String {
+    let mut query_str = format!("DELETE FROM {} WHERE {} = ", table, edge.to.field);
+    append_id_to_query(&mut query_str, from_id_type, from_id);
+    if let Some(id) = to_id {
+        query_str.push_str(format!(" AND {} = ", to_field).as_str());
+        append_id_to_query(&mut query_str, to_id_type, id);
+    }
+    append_predicate(&mut query_str, edge.row_predicate().as_ref());
+    return query_str;
+}
+
+fn delete_direct_edge_query(
+    from: &DelfObject,
+    to: &DelfObject,
+    from_id: &String,
+    to_id: Option<&String>,
+    edge: &DelfEdge,
+) -> String {
+    if let Some(from_type) = &edge.from {
+        // the edge is the field on the source's row holding the id it points to
+        let field = &from_type.field;
+        let to_id_sql = |id: &String| {
+            let mut id_str = String::new();
+            append_id_to_query(&mut id_str, &to.id_type, id);
+            id_str
+        };
+        let cleared = to_id_sql(&edge::cleared_id(&to.id_type));
+        let (value, condition) = match (from_type.format, to_id) {
+            (Some(format), Some(id)) => (
+                list_without(field, format, id),
+                Predicate::contains(field, format, &to.id_type, id).to_sql(),
+            ),
+            (Some(ListFormat::JsonArray), None) => (
+                String::from("JSON_ARRAY()"),
+                format!("JSON_LENGTH({}) > 0", field),
+            ),
+            (Some(ListFormat::Csv), None) => (String::from("''"), format!("{} <> ''", field)),
+            (None, Some(id)) => (cleared, format!("{} = {}", field, to_id_sql(id))),
+            (None, None) => (cleared.clone(), format!("{} <> {}", field, cleared)),
+        };
         let mut query_str = format!(
             "UPDATE {} SET {} = {} WHERE {} = ",
-            to.name, edge.to.field, default_value, edge.to.field
+            from.name, field, value, from.id_field
         );
-        self.append_id_to_query(&mut query_str, &to.id_type, from_id);
-        let num_rows = diesel::sql_query(query_str)
-            .execute(&self.connection)
+        append_id_to_query(&mut query_str, &from.id_type, from_id);
+        query_str.push_str(format!(" AND ({})", condition).as_str());
+        append_predicate(&mut query_str, edge.row_predicate().as_ref());
+        return query_str;
+    }
+
+    // the field holds the id of the object the edge starts from
+    let default_value;
+    match from.id_type.to_lowercase().as_str() {
+        "string" => default_value = "''",
+        "number" => default_value = "0",
+        _ => panic!("Unrecognized id type"),
+    }
+    let mut query_str = format!(
+        "UPDATE {} SET {} = {} WHERE {} = ",
+        to.name, edge.to.field, default_value, edge.to.field
+    );
+    append_id_to_query(&mut query_str, &from.id_type, from_id);
+    if let Some(id) = to_id {
+        query_str.push_str(format!(" AND {} = ", to.id_field).as_str());
+        append_id_to_query(&mut query_str, &to.id_type, id);
+    }
+    append_predicate(&mut query_str, edge.row_predicate().as_ref());
+    return query_str;
+}
+
+// Add a predicate to the end of a query's WHERE clause
+fn append_predicate(query_str: &mut String, predicate: Option<&Predicate>) {
+    match predicate {
+        Some(p) => query_str.push_str(&format!(" AND ({})", p.to_sql())),
+        None => (),
+    }
+}
+
+// The SQL for a list of ids in a field with one id removed, keeping the order of the rest.  A JSON array has the position of the id found with JSON_TABLE, which needs MySQL 8.0.4 or later.
+fn list_without(field: &String, format: ListFormat, id: &String) -> String {
+    let id = sql_literal(&serde_json::Value::String(id.clone()));
+    match format {
+        ListFormat::JsonArray => format!(
+            "JSON_REMOVE({}, (SELECT CONCAT('$[', e.i - 1, ']') FROM JSON_TABLE({}, '$[*]' COLUMNS (i FOR ORDINALITY, v JSON PATH '$')) AS e WHERE JSON_UNQUOTE(e.v) = {} LIMIT 1))",
+            field, field, id
+        ),
+        ListFormat::Csv => format!(
+            "TRIM(BOTH ',' FROM REPLACE(CONCAT(',', REPLACE({}, ' ', ''), ','), CONCAT(',', {}, ','), ','))",
+            field, id
+        ),
+    }
+}
+
+// Format a json value from a row as a mysql literal
+fn sql_literal(value: &serde_json::Value) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+    match value {
+        serde_json::Value::Null => String::from("NULL"),
+        serde_json::Value::Bool(b) => String::from(if *b { "1" } else { "0" }),
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) => quote(s),
+        v => quote(&v.to_string()),
+    }
+}
+
+impl DieselConnection {
+    // Check if a mapping table has a row referring to the object instance
+    fn has_mapping_row(&self, obj: &DelfObject, id: &String, edge: &DelfEdge, map_table: &String) -> bool {
+        let mut query_str = format!(
+            "SELECT count(*) as count FROM {} WHERE {} = ",
+            map_table, obj.id_field
+        );
+        append_id_to_query(&mut query_str, &obj.id_type, id);
+        append_predicate(&mut query_str, edge.row_predicate().as_ref());
+        let res = diesel::sql_query(query_str)
+            .load::<ValidationResult>(&self.connection)
             .unwrap();
 
-        if num_rows == 0 {
-            return false;
-        } else {
-            return true;
-        }
+        return res[0].count > 0;
     }
 
-    fn append_id_to_query(&self, query_str: &mut String, id_type: &String, id: &String) {
+    // The lowercase data type of a column, such as `datetime`
+    fn column_type(&self, table: &String, column: &String) -> String {
+        let res = diesel::sql_query(format!(
+            "SELECT LOWER(DATA_TYPE) as column_name FROM information_schema.columns WHERE table_schema = DATABASE() AND table_name = '{}' AND column_name = '{}'",
+            table, column
+        ))
+        .load::<ColumnResult>(&self.connection)
+        .unwrap();
+        return res.into_iter().map(|c| c.column_name).next().unwrap_or_default();
+    }
+
+    // Run a query selecting ids as `id_field`
+    fn load_ids(&self, query_str: String, id_type: &String) -> Vec<String> {
+        let query = diesel::sql_query(query_str);
+
+        let mut obj_ids = Vec::new();
+
         match id_type.to_lowercase().as_str() {
-            "string" => query_str.push_str(format!("'{}'", id).as_str()),
-            "number" => query_str.push_str(format!("{}", id).as_str()),
+            "string" => {
+                let res = query.load::<ObjectIdStrResult>(&self.connection).unwrap();
+                for o_id in res {
+                    obj_ids.push(o_id.id_field)
+                }
+            }
+            "number" => {
+                let res = query.load::<ObjectIdIntResult>(&self.connection).unwrap();
+                for o_id in res {
+                    obj_ids.push(o_id.id_field.to_string())
+                }
+            }
             _ => panic!("Unrecognized id type"),
         }
+
+        return obj_ids;
+    }
+}
+
+// Add an id to a query, quoted and escaped if it's a string, or parsed if it's a number, so it's never written into the query as given
+fn append_id_to_query(query_str: &mut String, id_type: &String, id: &String) {
+    match id_type.to_lowercase().as_str() {
+        "string" => query_str.push_str(&sql_literal(&serde_json::Value::String(id.clone()))),
+        "number" => match id.parse::<i64>() {
+            Ok(n) => query_str.push_str(&n.to_string()),
+            Err(_) => panic!("{} isn't a number id", id),
+        },
+        _ => panic!("Unrecognized id type"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = "
+object_type:
+  name: paper
+  storage: s
+  deletion: directly
+  id: id
+  edge_types:
+    - name: paper_comment
+      to:
+        object_type: comment
+        field: commentable_id
+        discriminator:
+          column: commentable_type
+          value: Paper
+      deletion: deep
+    - name: paper_authors
+      from:
+        field: author_ids
+        format: json_array
+      to:
+        object_type: contact
+      deletion: refcount
+    - name: paper_tags
+      from:
+        field: tag_ids
+        format: csv
+      to:
+        object_type: tag
+      deletion: shallow
+---
+object_type:
+  name: contact
+  storage: s
+  deletion: by_any
+  id: handle
+  id_type: string
+  edge_types:
+    - name: contact_paper
+      to:
+        object_type: paper
+        field: handle
+        mapping_table: authorship
+      deletion: shallow
+    - name: contact_lead
+      to:
+        object_type: paper
+        field: lead_handle
+      deletion: shallow
+    - name: contact_projects
+      to:
+        object_type: project
+        path:
+          - table: team_membership
+            from: handle
+            to: team_id
+          - table: team_project
+            from: team_id
+            to: project_id
+      deletion: shallow
+---
+object_type:
+  name: comment
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+---
+object_type:
+  name: tag
+  storage: s
+  deletion: directly
+  id: id
+  edge_types: []
+---
+object_type:
+  name: project
+  storage: s
+  deletion: by_any
+  id: id
+  edge_types: []
+";
+
+    // The SQL deleting an edge from `from_id`, to `to_id` if given
+    fn edge_sql(edge_name: &str, from_id: &str, to_id: Option<&str>) -> String {
+        let (graph, _) = crate::graph::test_graph(SCHEMA);
+        let edge = graph.get_edge(&String::from(edge_name));
+        let from = graph.get_edge_source(edge);
+        let to = graph.get_object(&edge.to.object_type);
+        let to_id = to_id.map(String::from);
+        return delete_edge_query(from, to, &String::from(from_id), to_id.as_ref(), edge);
+    }
+
+    #[test]
+    fn deletes_polymorphic_edges_of_their_type() {
+        assert_eq!(
+            edge_sql("paper_comment", "1", Some("10")),
+            "UPDATE comment SET commentable_id = 0 WHERE commentable_id = 1 AND id = 10 AND (commentable_type = 'Paper')"
+        );
+        assert_eq!(
+            edge_sql("paper_comment", "1", None),
+            "UPDATE comment SET commentable_id = 0 WHERE commentable_id = 1 AND (commentable_type = 'Paper')"
+        );
+    }
+
+    #[test]
+    fn removes_ids_from_lists() {
+        assert_eq!(
+            edge_sql("paper_authors", "10", Some("o'neil")),
+            "UPDATE paper SET author_ids = JSON_REMOVE(author_ids, (SELECT CONCAT('$[', e.i - 1, ']') FROM JSON_TABLE(author_ids, '$[*]' COLUMNS (i FOR ORDINALITY, v JSON PATH '$')) AS e WHERE JSON_UNQUOTE(e.v) = 'o\\'neil' LIMIT 1)) WHERE id = 10 AND (JSON_CONTAINS(author_ids, JSON_QUOTE('o\\'neil')))"
+        );
+        assert_eq!(
+            edge_sql("paper_authors", "10", None),
+            "UPDATE paper SET author_ids = JSON_ARRAY() WHERE id = 10 AND (JSON_LENGTH(author_ids) > 0)"
+        );
+        assert_eq!(
+            edge_sql("paper_tags", "10", Some("6")),
+            "UPDATE paper SET tag_ids = TRIM(BOTH ',' FROM REPLACE(CONCAT(',', REPLACE(tag_ids, ' ', ''), ','), CONCAT(',', '6', ','), ',')) WHERE id = 10 AND (FIND_IN_SET('6', REPLACE(tag_ids, ' ', '')) > 0)"
+        );
+        assert_eq!(
+            edge_sql("paper_tags", "10", None),
+            "UPDATE paper SET tag_ids = '' WHERE id = 10 AND (tag_ids <> '')"
+        );
+    }
+
+    #[test]
+    fn deletes_the_first_table_of_paths() {
+        assert_eq!(
+            edge_sql("contact_projects", "ada", Some("5")),
+            "DELETE FROM team_membership WHERE handle = 'ada' AND team_id = 5"
+        );
+        assert_eq!(
+            edge_sql("contact_projects", "ada", None),
+            "DELETE FROM team_membership WHERE handle = 'ada'"
+        );
+    }
+
+    #[test]
+    fn quotes_string_ids() {
+        assert_eq!(
+            edge_sql("contact_paper", "o'neil", None),
+            "DELETE FROM authorship WHERE handle = 'o\\'neil'"
+        );
+        assert_eq!(
+            edge_sql("contact_paper", "ada", Some("10")),
+            "DELETE FROM authorship WHERE handle = 'ada' AND id = 10"
+        );
+        assert_eq!(
+            edge_sql("contact_lead", "ada", Some("10")),
+            "UPDATE paper SET lead_handle = '' WHERE lead_handle = 'ada' AND id = 10"
+        );
+        assert_eq!(
+            edge_sql("contact_lead", "ada", None),
+            "UPDATE paper SET lead_handle = '' WHERE lead_handle = 'ada'"
+        );
     }
 }
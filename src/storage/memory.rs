@@ -0,0 +1,419 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+pub use super::DelfStorageConnection;
+use crate::graph::{
+    edge::{self, DelfEdge},
+    object::{Anonymization, DelfObject, Replacement},
+    predicate::Predicate,
+};
+use super::Row as JsonRow;
+
+type Row = HashMap<String, String>;
+
+// The connections made through the `memory` plugin, by url, so every connection to a url shares its tables
+static CONNECTIONS: Mutex<Option<HashMap<String, MemoryConnection>>> = Mutex::new(None);
+
+/// An in-memory storage used to test the graph algorithms without a database.  Tables are lists of rows, and every value is a string.  Cleared direct edges are set to `0`, except edges stored on their source pointing to string ids, which are set to an empty string, and null values are missing from their row.  Clones share the same tables, so a test can keep a handle to a connection owned by a graph.  Connections made through the `memory` plugin share the tables of their url.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryConnection {
+    pub tables: Arc<Mutex<HashMap<String, Vec<Row>>>>,
+    /// The (table, id) of each object instance deleted, in the order they were deleted
+    pub deleted: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl MemoryConnection {
+    /// Add a row to a table, given as (column, value) pairs
+    pub fn insert(&self, table: &str, row: &[(&str, &str)]) {
+        self.tables
+            .lock()
+            .unwrap()
+            .entry(String::from(table))
+            .or_insert_with(Vec::new)
+            .push(
+                row.iter()
+                    .map(|(k, v)| (String::from(*k), String::from(*v)))
+                    .collect(),
+            );
+    }
+
+    /// Get the values of a column of a table
+    pub fn column(&self, table: &str, column: &str) -> Vec<String> {
+        match self.tables.lock().unwrap().get(table) {
+            Some(rows) => rows.iter().map(|r| r[column].clone()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn matching(
+        &self,
+        table: &String,
+        field: &String,
+        value: &String,
+        predicate: Option<&Predicate>,
+    ) -> Vec<Row> {
+        match self.tables.lock().unwrap().get(table) {
+            Some(rows) => rows
+                .iter()
+                .filter(|r| r.get(field) == Some(value) && predicate.map_or(true, |p| p.matches(r)))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl DelfStorageConnection for MemoryConnection {
+    fn connect(database_url: &str) -> MemoryConnection {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        connections
+            .get_or_insert_with(HashMap::new)
+            .entry(String::from(database_url))
+            .or_insert_with(MemoryConnection::default)
+            .clone()
+    }
+
+    fn get_object_ids(
+        &self,
+        from_id: &String,
+        _from_id_type: &String,
+        edge_field: &String,
+        table: &String,
+        id_field: &String,
+        _id_type: &String,
+        predicate: Option<&Predicate>,
+    ) -> Vec<String> {
+        self.matching(table, edge_field, from_id, predicate)
+            .iter()
+            .map(|r| r[id_field].clone())
+            .collect()
+    }
+
+    fn get_object_ids_by_time(
+        &self,
+        table: &String,
+        time_field: &String,
+        id_field: &String,
+        _id_type: &String,
+        before: u64,
+    ) -> Vec<String> {
+        match self.tables.lock().unwrap().get(table) {
+            Some(rows) => rows
+                .iter()
+                .filter(|r| {
+                    r.get(time_field)
+                        .and_then(|t| t.parse::<u64>().ok())
+                        .map_or(false, |t| t < before)
+                })
+                .map(|r| r[id_field].clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_soft_deleted_ids(&self, obj: &DelfObject, field: &String, before: u64) -> Vec<String> {
+        return self.get_object_ids_by_time(&obj.name, field, &obj.id_field, &obj.id_type, before);
+    }
+
+    fn get_object_ids_matching(
+        &self,
+        table: &String,
+        id_field: &String,
+        _id_type: &String,
+        predicate: &Predicate,
+    ) -> Vec<String> {
+        match self.tables.lock().unwrap().get(table) {
+            Some(rows) => rows
+                .iter()
+                .filter(|r| predicate.matches(r))
+                .map(|r| r[id_field].clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_object_ids_page(
+        &self,
+        table: &String,
+        id_field: &String,
+        id_type: &String,
+        predicate: &Predicate,
+        after: Option<&String>,
+        limit: usize,
+    ) -> Vec<String> {
+        let mut ids = self.get_object_ids_matching(table, id_field, id_type, predicate);
+        ids.sort_by(|a, b| id_order(a, b));
+        ids.into_iter()
+            .filter(|id| after.map_or(true, |after| id_order(id, after) == Ordering::Greater))
+            .take(limit)
+            .collect()
+    }
+
+    fn delete_edge(
+        &self,
+        from: &DelfObject,
+        to: &DelfObject,
+        from_id: &String,
+        to_id: Option<&String>,
+        edge: &DelfEdge,
+    ) -> bool {
+        let mut tables = self.tables.lock().unwrap();
+        let predicate = edge.row_predicate();
+        if let Some(from_type) = &edge.from {
+            let field = &from_type.field;
+            if let Some(format) = from_type.format {
+                // an id is removed from the list, or the list emptied
+                return match tables.get_mut(&from.name) {
+                    Some(rows) => {
+                        let mut changed = false;
+                        for row in rows.iter_mut().filter(|r| {
+                            r.get(&from.id_field) == Some(from_id)
+                                && predicate.as_ref().map_or(true, |p| p.matches(r))
+                        }) {
+                            let value = match row.get(field) {
+                                Some(v) => v.clone(),
+                                None => continue,
+                            };
+                            let ids = format.split(&value);
+                            let updated = match to_id {
+                                Some(id) if ids.contains(id) => format.remove(&value, id),
+                                None if ids.len() > 0 => String::from(format.empty()),
+                                _ => continue,
+                            };
+                            row.insert(field.clone(), updated);
+                            changed = true;
+                        }
+                        changed
+                    }
+                    None => false,
+                };
+            }
+            let cleared = edge::cleared_id(&to.id_type);
+            return match tables.get_mut(&from.name) {
+                Some(rows) => {
+                    let mut changed = false;
+                    for row in rows.iter_mut().filter(|r| {
+                        r.get(&from.id_field) == Some(from_id)
+                            && predicate.as_ref().map_or(true, |p| p.matches(r))
+                            && match to_id {
+                                Some(id) => r.get(field) == Some(id),
+                                None => r.get(field).map_or(false, |v| *v != cleared),
+                            }
+                    }) {
+                        row.insert(field.clone(), cleared.clone());
+                        changed = true;
+                    }
+                    changed
+                }
+                None => false,
+            };
+        }
+
+        let field = &edge.to.field;
+        // the rows of a path's first table are deleted, and `to_id` is the id they lead to in the next table
+        let (mapping_table, to_field) = match &edge.to.path {
+            Some(hops) => (Some(&hops[0].table), &hops[0].to),
+            None => (edge.to.mapping_table.as_ref(), &to.id_field),
+        };
+        let matches = |r: &Row| {
+            r.get(field) == Some(from_id)
+                && predicate.as_ref().map_or(true, |p| p.matches(r))
+                && match to_id {
+                    Some(id) => r.get(to_field) == Some(id),
+                    None => true,
+                }
+        };
+
+        match mapping_table {
+            Some(table) => match tables.get_mut(table) {
+                Some(rows) => {
+                    let before = rows.len();
+                    rows.retain(|r| !matches(r));
+                    rows.len() != before
+                }
+                None => false,
+            },
+            None => match tables.get_mut(&to.name) {
+                Some(rows) => {
+                    let mut changed = false;
+                    for row in rows.iter_mut().filter(|r| matches(r)) {
+                        row.insert(field.clone(), edge::cleared_id(&from.id_type));
+                        changed = true;
+                    }
+                    changed
+                }
+                None => false,
+            },
+        }
+    }
+
+    fn delete_object(&self, obj: &DelfObject, id: &String) -> bool {
+        let deleted = match self.tables.lock().unwrap().get_mut(&obj.name) {
+            Some(rows) => {
+                let before = rows.len();
+                rows.retain(|r| r.get(&obj.id_field) != Some(id));
+                rows.len() != before
+            }
+            None => false,
+        };
+        if deleted {
+            self.deleted.lock().unwrap().push((obj.name.clone(), id.clone()));
+        }
+        deleted
+    }
+
+    fn soft_delete_object(&self, obj: &DelfObject, id: &String, field: &String, time: u64) -> bool {
+        match self.tables.lock().unwrap().get_mut(&obj.name) {
+            Some(rows) => {
+                let mut changed = false;
+                for row in rows
+                    .iter_mut()
+                    .filter(|r| r.get(&obj.id_field) == Some(id) && !r.contains_key(field))
+                {
+                    row.insert(field.clone(), time.to_string());
+                    changed = true;
+                }
+                changed
+            }
+            None => false,
+        }
+    }
+
+    fn anonymize_object(&self, obj: &DelfObject, id: &String, fields: &Vec<Anonymization>) -> bool {
+        match self.tables.lock().unwrap().get_mut(&obj.name) {
+            Some(rows) => {
+                let mut changed = false;
+                for row in rows.iter_mut().filter(|r| r.get(&obj.id_field) == Some(id)) {
+                    for a in fields.iter() {
+                        match &a.replacement {
+                            Replacement::Null => {
+                                row.remove(&a.field);
+                            }
+                            Replacement::Constant(value) => {
+                                row.insert(a.field.clone(), value.clone());
+                            }
+                            Replacement::Hash => {
+                                let mut hasher = DefaultHasher::new();
+                                row.get(&a.field).hash(&mut hasher);
+                                row.insert(a.field.clone(), format!("{:x}", hasher.finish()));
+                            }
+                        }
+                    }
+                    changed = true;
+                }
+                changed
+            }
+            None => false,
+        }
+    }
+
+    fn validate_edge(&self, _edge: &DelfEdge) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn validate_object(&self, _obj: &DelfObject) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn columns(&self, table: &String) -> Vec<String> {
+        let mut columns: Vec<String> = Vec::new();
+        if let Some(rows) = self.tables.lock().unwrap().get(table) {
+            for row in rows.iter() {
+                for column in row.keys() {
+                    if !columns.contains(column) {
+                        columns.push(column.clone());
+                    }
+                }
+            }
+        }
+        columns.sort();
+        columns
+    }
+
+    fn has_edge(&self, obj: &DelfObject, id: &String, edge: &DelfEdge) -> bool {
+        if let Some(table) = &edge.to.mapping_table {
+            return self.matching(table, &obj.id_field, id, edge.row_predicate().as_ref()).len() > 0;
+        }
+        let live = obj.live_predicate();
+        self.matching(&obj.name, &obj.id_field, id, edge.row_predicate().as_ref())
+            .iter()
+            .filter(|r| live.as_ref().map_or(true, |p| p.matches(r)))
+            .any(|r| r.get(&edge.to.field).map_or(false, |v| v != "0"))
+    }
+
+    fn has_object(&self, obj: &DelfObject, id: &String) -> bool {
+        self.matching(&obj.name, &obj.id_field, id, obj.live_predicate().as_ref()).len() > 0
+    }
+
+    fn get_rows(
+        &self,
+        table: &String,
+        field: &String,
+        _value_type: &String,
+        value: &String,
+        predicate: Option<&Predicate>,
+    ) -> Vec<JsonRow> {
+        self.matching(table, field, value, predicate)
+            .into_iter()
+            .map(|r| {
+                r.into_iter()
+                    .map(|(k, v)| (k, serde_json::Value::String(v)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn insert_rows(&self, table: &String, rows: &Vec<JsonRow>) -> usize {
+        let mut tables = self.tables.lock().unwrap();
+        let table_rows = tables.entry(table.clone()).or_insert_with(Vec::new);
+        for row in rows.iter() {
+            table_rows.push(row.iter().map(|(k, v)| (k.clone(), as_string(v))).collect());
+        }
+        rows.len()
+    }
+
+    fn update_field(
+        &self,
+        table: &String,
+        id_field: &String,
+        field: &String,
+        rows: &Vec<JsonRow>,
+    ) -> usize {
+        let mut tables = self.tables.lock().unwrap();
+        let mut updated = 0;
+        if let Some(table_rows) = tables.get_mut(table) {
+            for row in rows.iter() {
+                for r in table_rows
+                    .iter_mut()
+                    .filter(|r| r.get(id_field) == Some(&as_string(&row[id_field])))
+                {
+                    // missing columns are null
+                    match &row[field] {
+                        serde_json::Value::Null => r.remove(field),
+                        value => r.insert(field.clone(), as_string(value)),
+                    };
+                    updated += 1;
+                }
+            }
+        }
+        updated
+    }
+}
+
+// Order ids as numbers if both are numbers, and as strings otherwise
+fn id_order(a: &String, b: &String) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+fn as_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
@@ -1,17 +1,26 @@
 use std::fmt::Debug;
 
-use crate::graph::{edge::DelfEdge, object::DelfObject};
+use crate::graph::{
+    edge::DelfEdge,
+    object::{Anonymization, DelfObject},
+    predicate::Predicate,
+};
+
+/// A row of a table, as a json object of column names to values
+pub type Row = serde_json::Map<String, serde_json::Value>;
 
 mod diesel;
+#[cfg(test)]
+pub(crate) mod memory;
 
-/// Trait defining the api for a DelF storage connection.
-pub trait DelfStorageConnection: Debug {
+/// Trait defining the api for a DelF storage connection.  Connections are sent to the threads running a parallel cascade, so must be `Send`.
+pub trait DelfStorageConnection: Debug + Send {
     /// Connect to the storage via a connection string
     fn connect(database_url: &str) -> Self
     where
         Self: Sized;
 
-    /// Get the object ids of the object an edge points to.
+    /// Get the object ids of the object an edge points to, from the rows of `table` matching the predicate if there is one.
     fn get_object_ids(
         &self,
         from_id: &String,
@@ -20,20 +29,46 @@ pub trait DelfStorageConnection: Debug {
         table: &String,
         id_field: &String,
         id_type: &String,
+        predicate: Option<&Predicate>,
     ) -> Vec<String>;
 
-    /// Get the object ids that have a `time_field` with a value before now.
+    /// Get the object ids that have a `time_field` with a value before the given time, in seconds since the unix epoch.
     fn get_object_ids_by_time(
         &self,
         table: &String,
         time_field: &String,
         id_field: &String,
         id_type: &String,
+        before: u64,
+    ) -> Vec<String>;
+
+    /// Get the ids of the instances of an object whose soft delete `field`, a date and time, marks them deleted before the given time, in seconds since the unix epoch.
+    fn get_soft_deleted_ids(&self, obj: &DelfObject, field: &String, before: u64) -> Vec<String>;
+
+    /// Get the ids from the `id_field` of the rows of `table` matching the predicate.
+    fn get_object_ids_matching(
+        &self,
+        table: &String,
+        id_field: &String,
+        id_type: &String,
+        predicate: &Predicate,
+    ) -> Vec<String>;
+
+    /// Get a page of the ids from the `id_field` of the rows of `table` matching the predicate, in order, with at most `limit` ids after the id `after` if given.
+    fn get_object_ids_page(
+        &self,
+        table: &String,
+        id_field: &String,
+        id_type: &String,
+        predicate: &Predicate,
+        after: Option<&String>,
+        limit: usize,
     ) -> Vec<String>;
 
-    /// Delete an edge instance.
+    /// Delete the instances of an edge from `from_id`, or only the one to `to_id` if given, only changing rows which match the edge's predicate if it has one.  An edge with a `from` field is cleared on the rows of the object it starts from, and an edge with a `path` deletes the rows of its first table, with `to_id` the id they lead to in the next table.
     fn delete_edge(
         &self,
+        from: &DelfObject,
         to: &DelfObject,
         from_id: &String,
         to_id: Option<&String>,
@@ -43,20 +78,60 @@ pub trait DelfStorageConnection: Debug {
     /// Delete an object instance.
     fn delete_object(&self, obj: &DelfObject, id: &String) -> bool;
 
-    /// Validate the edge exists in the storage as described in the struct
+    /// Mark an object instance deleted by setting `field` to the date and time given in seconds since the unix epoch, unless it is already marked.  Returns whether the instance was marked.
+    fn soft_delete_object(&self, obj: &DelfObject, id: &String, field: &String, time: u64) -> bool;
+
+    /// Replace the given fields of an object instance instead of deleting it.  Returns whether the instance was changed.
+    fn anonymize_object(&self, obj: &DelfObject, id: &String, fields: &Vec<Anonymization>) -> bool;
+
+    /// Validate the edge exists in the storage as described in the struct, including the columns its predicate refers to
     fn validate_edge(&self, edge: &DelfEdge) -> Result<(), String>;
 
     /// Validate the object exists in the storage as described in the struct
     fn validate_object(&self, obj: &DelfObject) -> Result<(), String>;
 
-    /// Check if an inbound edge exists for a given object instance.
+    /// Get the names of the columns of a table
+    fn columns(&self, table: &String) -> Vec<String>;
+
+    /// Check if an inbound edge exists for a given object instance, either as its field or as a row of the edge's mapping table, only counting rows which match the edge's predicate if it has one.  Soft deleted instances have no edges through their field.
     fn has_edge(&self, obj: &DelfObject, id: &String, edge: &DelfEdge) -> bool;
+
+    /// Check if an object instance exists and isn't soft deleted.
+    fn has_object(&self, obj: &DelfObject, id: &String) -> bool;
+
+    /// Get the rows of a table where the field has the given value and which match the predicate if there is one, with `value_type` the id type of the value.
+    fn get_rows(
+        &self,
+        table: &String,
+        field: &String,
+        value_type: &String,
+        value: &String,
+        predicate: Option<&Predicate>,
+    ) -> Vec<Row>;
+
+    /// Insert rows into a table, returning the number of rows inserted.
+    fn insert_rows(&self, table: &String, rows: &Vec<Row>) -> usize;
+
+    /// Set the field of each row in a table back to its value in the given rows, matching rows on `id_field`.  Returns the number of rows updated.
+    fn update_field(&self, table: &String, id_field: &String, field: &String, rows: &Vec<Row>) -> usize;
+}
+
+/// Whether there is a DelfStorageConnection with the plugin name
+pub fn has_plugin(plugin: &str) -> bool {
+    match plugin {
+        "diesel" => true,
+        #[cfg(test)]
+        "memory" => true,
+        _ => false,
+    }
 }
 
 /// Given the name of the plugin (trait implementor) and the connection string, return an instance of that DelfStorageConnection.
 pub fn get_connection(plugin: &str, url: &str) -> Box<dyn DelfStorageConnection> {
     match plugin {
         "diesel" => Box::new(diesel::DieselConnection::connect(url)),
+        #[cfg(test)]
+        "memory" => Box::new(memory::MemoryConnection::connect(url)),
         _ => panic!("no DelfStorageConnection with that name"),
     }
 }
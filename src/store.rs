@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+static FILE_LOCKS: Mutex<BTreeMap<String, Arc<Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// The current time in seconds since the unix epoch
+pub(crate) fn now() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+}
+
+/// Create an id which is unique within the process and unlikely to repeat across processes
+pub(crate) fn new_id() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let count = ID_COUNTER.fetch_add(1, Ordering::SeqCst);
+    return format!("{:x}-{:x}-{:x}", now.as_secs(), now.subsec_nanos(), count);
+}
+
+/// Load a value from a json file, which is the default (e.g. an empty list) if the file doesn't exist yet
+pub(crate) fn load<T: DeserializeOwned + Default>(path: &String) -> Result<T, String> {
+    if !Path::new(path).exists() {
+        return Ok(T::default());
+    }
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    return serde_json::from_str(&contents).map_err(|e| format!("couldn't parse {}: {}", path, e));
+}
+
+/// Save a value to a json file, replacing the file in one step so a crash can't leave it half written
+pub(crate) fn save<T: Serialize>(path: &String, value: &T) -> Result<(), String> {
+    return replace(path, serde_json::to_string_pretty(value).unwrap());
+}
+
+/// Replace the contents of a file in one step, by writing them next to it then renaming over it
+pub(crate) fn replace(path: &String, contents: String) -> Result<(), String> {
+    let tmp_path = format!("{}.tmp", path);
+    std::fs::write(&tmp_path, contents).map_err(|e| format!("couldn't write {}: {}", tmp_path, e))?;
+    return std::fs::rename(&tmp_path, path).map_err(|e| format!("couldn't write {}: {}", path, e));
+}
+
+/// Run `f` holding the process-wide lock for the file at the path, so reading, changing and saving the file can't interleave with another thread doing the same
+pub(crate) fn locked<R, F: FnOnce() -> R>(path: &String, f: F) -> R {
+    let lock = FILE_LOCKS
+        .lock()
+        .unwrap()
+        .entry(path.clone())
+        .or_insert_with(|| Arc::new(Mutex::new(())))
+        .clone();
+    // a panic while the lock was held can't have left the file half written, as saves replace it in one step
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    return f();
+}